@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+
+/// Observability snapshot of a tenant's [`WalRedoManager`](../../pageserver/src/walredo.rs).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WalRedoManagerStatus {
+    pub last_redo_at: Option<DateTime<Utc>>,
+    pub process: Option<WalRedoManagerProcessStatus>,
+    pub pool: WalRedoManagerPoolStatus,
+    pub last_failure: Option<WalRedoManagerLastFailure>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WalRedoManagerProcessStatus {
+    pub pid: u32,
+}
+
+/// Occupancy of the warm wal-redo process pool.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WalRedoManagerPoolStatus {
+    /// Configured pool cap: the most processes checked out at once, and the
+    /// most kept alive per `pg_version`.
+    pub size: usize,
+    /// Processes currently parked and available for reuse.
+    pub idle: usize,
+    /// Processes currently checked out and applying WAL.
+    pub in_use: usize,
+}
+
+/// The most recent wal-redo failure, if any.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WalRedoManagerLastFailure {
+    /// Number of attempts made, including the one that produced this failure.
+    pub attempts: u32,
+    /// Whether the failure was classified as transient. Transient failures
+    /// are retried until the retry budget is used up; deterministic ones are not.
+    pub transient: bool,
+}
@@ -23,14 +23,131 @@
 
 use std::{
     cell::UnsafeCell,
-    sync::Mutex,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     task::{Poll, Waker},
     time::Duration,
 };
 
+use arc_swap::ArcSwap;
+use dashmap::DashMap;
 use pin_list::{Node, NodeData, PinList};
 use tokio::time::Instant;
 
+/// Source of the current time for the rate limiter.
+///
+/// Every `add_tokens`/`acquire` needs to read "now". Under very high request
+/// rates the syscall-backed [`tokio::time::Instant::now`] read starts to
+/// dominate, so this is pluggable: the default [`TokioClock`] reads the real
+/// clock each call, while [`CoarseClock`] amortizes the read across a whole
+/// resolution window. Tests can inject a [`ManualClock`] to drive time
+/// explicitly, without relying on [`tokio::time::advance`] being globally
+/// visible.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The default [`Clock`]: reads the real tokio clock on every call. Honours
+/// `tokio::time`'s paused/auto-advanced time, so it is also what the paused-time
+/// tests run against.
+#[derive(Clone, Copy, Default)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now()
+    }
+}
+
+/// A [`Clock`] that caches the current instant and refreshes it from a single
+/// background timer at a configurable resolution, so millions of `acquire`
+/// calls share one timestamp read instead of each performing their own. The
+/// same trick high-QPS servers use to amortize time reads.
+///
+/// The cached time is stored as a nanosecond offset from a fixed base instant,
+/// updated lock-free. `now()` is therefore coarse to within the refresh
+/// resolution; pick a resolution well below the limiter's `cost` so rate
+/// accuracy is unaffected.
+#[derive(Clone)]
+pub struct CoarseClock {
+    base: Instant,
+    offset_nanos: Arc<AtomicU64>,
+}
+
+impl CoarseClock {
+    pub fn new() -> Self {
+        CoarseClock {
+            base: Instant::now(),
+            offset_nanos: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Spawns the background timer that refreshes the cached time every
+    /// `resolution`. The task holds only a weak reference, so it exits once the
+    /// last clone of this clock is dropped.
+    pub fn spawn_refresh_task(&self, resolution: Duration) {
+        let base = self.base;
+        let offset = Arc::downgrade(&self.offset_nanos);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(resolution);
+            loop {
+                ticker.tick().await;
+                let Some(offset) = offset.upgrade() else {
+                    break;
+                };
+                // A single real clock read, shared by every `now()` until the
+                // next tick.
+                let nanos = base.elapsed().as_nanos() as u64;
+                offset.store(nanos, Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+impl Default for CoarseClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for CoarseClock {
+    fn now(&self) -> Instant {
+        self.base + Duration::from_nanos(self.offset_nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// A manually-driven [`Clock`] for tests: time only moves when
+/// [`ManualClock::advance`] is called, so rate-limit behaviour can be exercised
+/// deterministically without `tokio::time` paused-mode globals.
+#[derive(Clone)]
+pub struct ManualClock {
+    base: Instant,
+    offset_nanos: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new(now: Instant) -> Self {
+        ManualClock {
+            base: now,
+            offset_nanos: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.offset_nanos
+            .fetch_add(by.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.base + Duration::from_nanos(self.offset_nanos.load(Ordering::Relaxed))
+    }
+}
+
 pub struct LeakyBucketConfig {
     /// This is the "time cost" of a single request unit.
     /// Should loosely represent how long it takes to handle a request unit in active resource time.
@@ -65,12 +182,42 @@ pub struct LeakyBucketState {
     /// This is inspired by the generic cell rate algorithm (GCRA) and works
     /// exactly the same as a leaky-bucket.
     pub empty_at: Instant,
+
+    /// A fixed pool of extra tokens, spent *before* `empty_at` is touched and
+    /// never refilled. Unlike seeding the steady bucket with
+    /// [`Self::with_initial_tokens`], this credit does not inflate the
+    /// steady-state burst window: it lets a freshly started connection absorb
+    /// one large initial spike (e.g. a warm cache preload) and, once exhausted,
+    /// behaviour is identical to a bucket without it. Modelled on the one-time
+    /// burst used by microVM I/O limiters.
+    pub one_time_burst: f64,
 }
 
 impl LeakyBucketState {
     pub fn with_initial_tokens(config: &LeakyBucketConfig, initial_tokens: f64) -> Self {
+        Self::with_initial_tokens_at(config, initial_tokens, Instant::now())
+    }
+
+    /// Like [`Self::with_initial_tokens`], but seeds the bucket relative to an
+    /// explicit `now` rather than reading the clock, so a [`RateLimiter`] built
+    /// on a pluggable [`Clock`] stays consistent with its time source.
+    pub fn with_initial_tokens_at(
+        config: &LeakyBucketConfig,
+        initial_tokens: f64,
+        now: Instant,
+    ) -> Self {
         LeakyBucketState {
-            empty_at: Instant::now() + config.cost.mul_f64(initial_tokens),
+            empty_at: now + config.cost.mul_f64(initial_tokens),
+            one_time_burst: 0.0,
+        }
+    }
+
+    /// Creates a bucket empty as of now with a one-time burst pool of
+    /// `one_time_burst` tokens. See [`Self::one_time_burst`].
+    pub fn with_one_time_burst(one_time_burst: f64) -> Self {
+        LeakyBucketState {
+            empty_at: Instant::now(),
+            one_time_burst,
         }
     }
 
@@ -79,6 +226,20 @@ impl LeakyBucketState {
         self.empty_at <= now
     }
 
+    /// Clamps `empty_at` so the bucket holds at most a full `bucket_width` of
+    /// tokens relative to `now`.
+    ///
+    /// A no-op in steady state — [`Self::add_tokens`] already maintains this
+    /// invariant on every successful add — but needed after `bucket_width` is
+    /// shrunk via [`RateLimiter::reconfigure`], otherwise the pre-shrink
+    /// `empty_at` would leave the bucket instantly overfull under the new width.
+    pub fn clamp_to_width(&mut self, config: &LeakyBucketConfig, now: Instant) {
+        let max_empty_at = now + config.bucket_width;
+        if self.empty_at > max_empty_at {
+            self.empty_at = max_empty_at;
+        }
+    }
+
     /// Immediately adds tokens to the bucket, if there is space.
     ///
     /// In a scenario where you are waiting for available rate,
@@ -86,6 +247,9 @@ impl LeakyBucketState {
     ///
     /// `n` is the number of tokens that will be filled in the bucket.
     ///
+    /// `now` is the current time, read from the caller's [`Clock`] so the time
+    /// source can be amortized under high throughput.
+    ///
     /// # Errors
     ///
     /// If there is not enough space, no tokens are added. Instead, an error is returned with the time when
@@ -94,10 +258,40 @@ impl LeakyBucketState {
         &mut self,
         config: &LeakyBucketConfig,
         started: Instant,
+        now: Instant,
         n: f64,
     ) -> Result<(), Instant> {
-        let now = Instant::now();
+        // Spend the one-time burst pool first; only the remainder goes through
+        // the GCRA check. The burst is debited only once the whole request is
+        // known to fit, preserving the "nothing added on error" contract.
+        let from_burst = self.one_time_burst.min(n);
+        let remainder = n - from_burst;
 
+        match self.check(config, started, now, remainder) {
+            Ok(new_empty_at) => {
+                self.one_time_burst -= from_burst;
+                self.empty_at = new_empty_at;
+                Ok(())
+            }
+            Err(allow_at) => Err(allow_at),
+        }
+    }
+
+    /// Computes the GCRA step of [`Self::add_tokens`] for `n` tokens, without
+    /// mutating the bucket: on success it returns the `empty_at` value to
+    /// commit, on failure the instant at which there will be space again. The
+    /// one-time burst pool is not consulted here.
+    ///
+    /// Splitting the pure computation out lets a [`MultiLeakyBucketState`] check
+    /// several buckets against a shared `now` and only commit once all of them
+    /// have space.
+    fn check(
+        &self,
+        config: &LeakyBucketConfig,
+        started: Instant,
+        now: Instant,
+        n: f64,
+    ) -> Result<Instant, Instant> {
         // invariant: started <= now
         debug_assert!(started <= now);
 
@@ -124,22 +318,220 @@ impl LeakyBucketState {
 
         match allow_at {
             Some(allow_at) if now < allow_at => Err(allow_at),
-            _ => {
-                self.empty_at = new_empty_at;
-                Ok(())
+            _ => Ok(new_empty_at),
+        }
+    }
+}
+
+/// The resource dimensions a [`MultiLeakyBucketState`] throttles independently.
+///
+/// Borrowed from the dual-bucket design used for virtio block/net throttling:
+/// one bucket sized in bytes for bandwidth, one sized in operations for IOPS.
+/// The discriminants double as the bucket array index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenType {
+    /// Bandwidth, counted in bytes.
+    Bytes = 0,
+    /// Operations / IOPS.
+    Ops = 1,
+}
+
+impl TokenType {
+    /// Number of distinct token types, i.e. the size of the bucket array.
+    pub const COUNT: usize = 2;
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// A [`LeakyBucketConfig`] per [`TokenType`], so a single limiter can enforce,
+/// e.g., 100 MB/s and 5000 ops/s simultaneously.
+pub struct MultiLeakyBucketConfig {
+    pub configs: [LeakyBucketConfig; TokenType::COUNT],
+}
+
+impl MultiLeakyBucketConfig {
+    pub fn new(configs: [LeakyBucketConfig; TokenType::COUNT]) -> Self {
+        Self { configs }
+    }
+}
+
+/// A bundle of independent [`LeakyBucketState`]s, one per [`TokenType`], that
+/// can be charged in a single atomic step.
+pub struct MultiLeakyBucketState {
+    buckets: [LeakyBucketState; TokenType::COUNT],
+}
+
+impl MultiLeakyBucketState {
+    pub fn new(buckets: [LeakyBucketState; TokenType::COUNT]) -> Self {
+        Self { buckets }
+    }
+
+    /// Adds tokens to every relevant bucket, committing only if *all* of them
+    /// have space.
+    ///
+    /// Each `(TokenType, count)` pair is charged against its bucket; repeated
+    /// types accumulate. The prospective `empty_at` for every bucket is computed
+    /// against a single shared `now` without mutating anything. If any bucket is
+    /// full, nothing is committed and the maximum `ready_at` across the blocked
+    /// buckets is returned, so a partial charge never leaves one bucket advanced
+    /// while another rejects. Callers waiting for rate should sleep until that
+    /// instant and retry, exactly as [`RateLimiter::acquire`] does for the
+    /// single-bucket case.
+    pub fn add_tokens(
+        &mut self,
+        config: &MultiLeakyBucketConfig,
+        started: Instant,
+        now: Instant,
+        reqs: &[(TokenType, f64)],
+    ) -> Result<(), Instant> {
+        debug_assert!(started <= now);
+
+        // Accumulate per-bucket demand first so several pairs targeting the same
+        // dimension are charged as one.
+        let mut counts = [0.0f64; TokenType::COUNT];
+        for &(ty, n) in reqs {
+            counts[ty.index()] += n;
+        }
+
+        let mut prospective = [None; TokenType::COUNT];
+        let mut ready_at: Option<Instant> = None;
+        for i in 0..TokenType::COUNT {
+            if counts[i] == 0.0 {
+                continue;
+            }
+            match self.buckets[i].check(&config.configs[i], started, now, counts[i]) {
+                Ok(new_empty_at) => prospective[i] = Some(new_empty_at),
+                Err(allow_at) => {
+                    ready_at = Some(ready_at.map_or(allow_at, |r: Instant| r.max(allow_at)));
+                }
+            }
+        }
+
+        // One blocked bucket is enough to reject the whole charge.
+        if let Some(ready_at) = ready_at {
+            return Err(ready_at);
+        }
+
+        for (bucket, new_empty_at) in self.buckets.iter_mut().zip(prospective) {
+            if let Some(new_empty_at) = new_empty_at {
+                bucket.empty_at = new_empty_at;
             }
         }
+        Ok(())
     }
 }
 
-pub struct RateLimiter {
+/// A per-key rate limiter: one [`LeakyBucketState`] per key, all sharing a
+/// single [`LeakyBucketConfig`], so callers can throttle per client IP / tenant
+/// / endpoint without managing a limiter per key by hand.
+///
+/// Buckets are created lazily on first use. The memory concern with such maps
+/// in large public-facing deployments is unbounded growth from one-shot keys;
+/// we lean on the GCRA invariant that a bucket is fully drained exactly when
+/// `empty_at <= now` ([`LeakyBucketState::bucket_is_empty`]) to reclaim them
+/// safely. A drained bucket is indistinguishable from a freshly created one, so
+/// removing it loses no rate: an entry re-acquired after being swept just starts
+/// from empty again, exactly as [`LeakyBucketState::add_tokens`]'s floor would
+/// have reset it in place. [`Self::cleanup`] sweeps all such entries, and
+/// [`Self::spawn_cleanup_task`] runs it on an interval.
+pub struct KeyedRateLimiter<K, C = TokioClock> {
     config: LeakyBucketConfig,
+    clock: C,
+    buckets: DashMap<K, LeakyBucketState>,
+}
+
+impl<K: Eq + Hash + Clone> KeyedRateLimiter<K, TokioClock> {
+    pub fn new(config: LeakyBucketConfig) -> Self {
+        Self::with_clock(config, TokioClock)
+    }
+}
+
+impl<K: Eq + Hash + Clone, C: Clock> KeyedRateLimiter<K, C> {
+    pub fn with_clock(config: LeakyBucketConfig, clock: C) -> Self {
+        KeyedRateLimiter {
+            config,
+            clock,
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// Charges `count` tokens to `key`'s bucket, creating it if necessary.
+    ///
+    /// Returns `Err(ready_at)` with the instant space frees up if the bucket is
+    /// full, mirroring [`LeakyBucketState::add_tokens`].
+    pub fn add_tokens(&self, key: K, started: Instant, count: f64) -> Result<(), Instant> {
+        let now = self.clock.now();
+        // A previously-drained entry for this key is reset by `add_tokens`'s own
+        // floor, so touching a stale empty bucket is equivalent to recreating
+        // it; only keys that are never re-acquired leak, and those are what
+        // `cleanup` reclaims.
+        let mut entry = self
+            .buckets
+            .entry(key)
+            .or_insert_with(|| LeakyBucketState {
+                empty_at: now,
+                one_time_burst: 0.0,
+            });
+        entry.add_tokens(&self.config, started, now, count)
+    }
+
+    /// Removes every bucket that is fully drained as of `now`. Cheap and safe:
+    /// an empty bucket carries no rate, so dropping it is equivalent to leaving
+    /// a fresh one to be recreated on the next `add_tokens`.
+    pub fn cleanup(&self, now: Instant) {
+        self.buckets.retain(|_, state| !state.bucket_is_empty(now));
+    }
+
+    /// Number of live buckets. Exposed mainly so tests can observe the map
+    /// shrinking back after a drain interval.
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+impl<K: Eq + Hash + Clone + Send + Sync + 'static, C: Clock + Send + Sync + 'static>
+    KeyedRateLimiter<K, C>
+{
+    /// Spawns a background task that calls [`Self::cleanup`] every `interval`.
+    /// The task holds only a weak reference, so it exits once the last strong
+    /// `Arc` to the limiter is dropped.
+    pub fn spawn_cleanup_task(self: &Arc<Self>, interval: Duration) {
+        let weak = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The immediate first tick is pointless on a fresh, empty map.
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(this) = weak.upgrade() else {
+                    break;
+                };
+                this.cleanup(this.clock.now());
+            }
+        });
+    }
+}
+
+pub struct RateLimiter<C = TokioClock> {
+    /// The live config. Held behind an [`ArcSwap`] so it can be hot-reloaded
+    /// from a config file / control plane while the limiter is serving traffic;
+    /// see [`RateLimiter::reconfigure`]. In-flight `acquire` calls re-read it on
+    /// every retry-loop iteration, so a change takes effect without dropping any
+    /// bucket state.
+    config: ArcSwap<LeakyBucketConfig>,
+    clock: C,
     state: UnsafeCell<LeakyBucketState>,
     queue: Mutex<Queue>,
 }
 
 // SAFETY: To access the state in the UnsafeCell, you must be holding the RateToken.
-unsafe impl Sync for RateLimiter {}
+unsafe impl<C: Sync> Sync for RateLimiter<C> {}
 
 struct RateToken;
 
@@ -259,14 +651,29 @@ impl std::future::Future for Enqueued<'_> {
     }
 }
 
-impl RateLimiter {
+impl RateLimiter<TokioClock> {
     pub fn with_initial_tokens(config: LeakyBucketConfig, initial_tokens: f64) -> Self {
+        Self::with_initial_tokens_and_clock(config, initial_tokens, TokioClock)
+    }
+}
+
+impl<C: Clock> RateLimiter<C> {
+    /// Like [`Self::with_initial_tokens`], but driven by an explicit [`Clock`].
+    /// Lets callers swap in [`CoarseClock`] for throughput or [`ManualClock`]
+    /// in tests without a tokio paused-time runtime.
+    pub fn with_initial_tokens_and_clock(
+        config: LeakyBucketConfig,
+        initial_tokens: f64,
+        clock: C,
+    ) -> Self {
         RateLimiter {
-            state: UnsafeCell::new(LeakyBucketState::with_initial_tokens(
+            state: UnsafeCell::new(LeakyBucketState::with_initial_tokens_at(
                 &config,
                 initial_tokens,
+                clock.now(),
             )),
-            config,
+            config: ArcSwap::from_pointee(config),
+            clock,
             queue: Mutex::new(Queue {
                 sleep_counter: 0,
                 // SAFETY: we make sure to only interact with the same queue
@@ -277,12 +684,206 @@ impl RateLimiter {
     }
 
     pub fn steady_rps(&self) -> f64 {
-        self.config.cost.as_secs_f64().recip()
+        self.config.load().cost.as_secs_f64().recip()
+    }
+
+    /// Atomically retunes the live limiter's rate and burst without dropping any
+    /// bucket state, so operators can adjust limits from a config file / control
+    /// plane while traffic is flowing.
+    ///
+    /// The new config is published immediately; in-flight `acquire` calls pick
+    /// it up on their next retry-loop iteration. Changing `cost` needs no state
+    /// fixup because `empty_at` is stored in absolute time. Shrinking
+    /// `bucket_width`, however, could leave the bucket instantly overfull, so we
+    /// clamp `empty_at` to the new width: if we can take the fairness token
+    /// without waiting we do it here, otherwise the task currently holding the
+    /// token clamps on its next iteration (see [`LeakyBucketState::clamp_to_width`]).
+    pub fn reconfigure(&self, new: LeakyBucketConfig) {
+        let new = Arc::new(new);
+        self.config.store(Arc::clone(&new));
+
+        #[allow(clippy::mut_mutex_lock, reason = "consistent with the queue drop impl")]
+        let mut q = self.queue.lock().unwrap();
+        if let Some(token) = q.token.take() {
+            let now = self.clock.now();
+            // SAFETY: we are holding the token.
+            unsafe {
+                let state = &mut *self.state.get();
+                state.clamp_to_width(&new, now);
+            }
+            match q.queue.cursor_front_mut().remove_current(token) {
+                Ok(waker) => waker.wake(),
+                Err(token) => q.token = Some(token),
+            }
+        }
     }
 
     /// returns true if we did throttle
     pub async fn acquire(&self, count: usize) -> bool {
-        let start = tokio::time::Instant::now();
+        let start = self.clock.now();
+
+        let mut entry = std::pin::pin!(Queue::wait(&self.queue));
+        let start_count = entry.as_mut().await;
+        let (_token, sleep_counter) = entry
+            .project()
+            .token
+            .as_mut()
+            .expect("token should be init if we returned from enqueued");
+
+        loop {
+            // Re-read the (possibly hot-reloaded) config each iteration.
+            let config = self.config.load();
+            let now = self.clock.now();
+            // SAFETY: we are holding the token.
+            let res = unsafe {
+                let state = &mut *self.state.get();
+                state.clamp_to_width(&config, now);
+                state.add_tokens(&config, start, now, count as f64)
+            };
+            match res {
+                Ok(()) => return start_count < *sleep_counter,
+                Err(ready_at) => {
+                    *sleep_counter += 1;
+                    tokio::time::sleep_until(ready_at).await;
+                }
+            }
+        }
+    }
+
+    /// Non-blocking acquire: grants `count` tokens only if the limiter is idle
+    /// and has space right now, never sleeping or enqueuing.
+    ///
+    /// Returns `Err(ready_at)` if the bucket is full, with the instant tokens
+    /// free up. If another task is ahead of us (holding the fairness token) we
+    /// also fail fast, returning `Err(now)` so the caller can retry or shed the
+    /// request rather than wait behind the queue.
+    pub fn try_acquire(&self, count: usize) -> Result<(), Instant> {
+        let now = self.clock.now();
+
+        #[allow(clippy::mut_mutex_lock, reason = "consistent with the queue drop impl")]
+        let mut q = self.queue.lock().unwrap();
+
+        // Only take the token if it is immediately available: nobody is draining
+        // and the queue is empty. Otherwise we'd be jumping the queue.
+        let Some(token) = q.token.take() else {
+            return Err(now);
+        };
+
+        let config = self.config.load();
+        // SAFETY: we are holding the token.
+        let res = unsafe {
+            let state = &mut *self.state.get();
+            state.clamp_to_width(&config, now);
+            state.add_tokens(&config, now, now, count as f64)
+        };
+
+        // We never sleep, so hand the token straight back: wake the next waiter
+        // if any, else park it on the queue. Mirrors the `Enqueued` drop path.
+        match q.queue.cursor_front_mut().remove_current(token) {
+            Ok(waker) => waker.wake(),
+            Err(token) => q.token = Some(token),
+        }
+
+        res
+    }
+
+    /// Like [`Self::acquire`], but gives up if tokens would not be available
+    /// until after `deadline`.
+    ///
+    /// Returns `true` if the tokens were granted, or `false` if the attempt
+    /// timed out. On timeout the in-flight enqueue is dropped, which passes the
+    /// fairness token on to the next waiter. Useful for latency-sensitive
+    /// callers that prefer shedding load over queueing indefinitely.
+    pub async fn acquire_timeout(&self, count: usize, deadline: Instant) -> bool {
+        let start = self.clock.now();
+
+        let mut entry = std::pin::pin!(Queue::wait(&self.queue));
+        let _start_count = entry.as_mut().await;
+        let (_token, sleep_counter) = entry
+            .project()
+            .token
+            .as_mut()
+            .expect("token should be init if we returned from enqueued");
+
+        loop {
+            // Re-read the (possibly hot-reloaded) config each iteration.
+            let config = self.config.load();
+            let now = self.clock.now();
+            // SAFETY: we are holding the token.
+            let res = unsafe {
+                let state = &mut *self.state.get();
+                state.clamp_to_width(&config, now);
+                state.add_tokens(&config, start, now, count as f64)
+            };
+            match res {
+                Ok(()) => return true,
+                Err(ready_at) => {
+                    if ready_at > deadline {
+                        // Abandon the attempt. Returning drops `entry`, whose
+                        // PinnedDrop releases the token to the next waiter.
+                        return false;
+                    }
+                    *sleep_counter += 1;
+                    tokio::time::sleep_until(ready_at).await;
+                }
+            }
+        }
+    }
+}
+
+/// Like [`RateLimiter`], but queued/fair access to a [`MultiLeakyBucketState`]
+/// instead of a single [`LeakyBucketState`], so several independent resource
+/// dimensions (e.g. bytes and ops) can be throttled together behind one
+/// `await`. Reuses the exact same [`Queue`]/[`Enqueued`] fairness machinery as
+/// [`RateLimiter`]: the queue itself holds no bucket-specific state.
+pub struct MultiRateLimiter<C = TokioClock> {
+    config: ArcSwap<MultiLeakyBucketConfig>,
+    clock: C,
+    state: UnsafeCell<MultiLeakyBucketState>,
+    queue: Mutex<Queue>,
+}
+
+// SAFETY: To access the state in the UnsafeCell, you must be holding the RateToken.
+unsafe impl<C: Sync> Sync for MultiRateLimiter<C> {}
+
+impl MultiRateLimiter<TokioClock> {
+    pub fn new(config: MultiLeakyBucketConfig, buckets: MultiLeakyBucketState) -> Self {
+        Self::with_clock(config, buckets, TokioClock)
+    }
+}
+
+impl<C: Clock> MultiRateLimiter<C> {
+    /// Like [`Self::new`], but driven by an explicit [`Clock`].
+    pub fn with_clock(config: MultiLeakyBucketConfig, buckets: MultiLeakyBucketState, clock: C) -> Self {
+        MultiRateLimiter {
+            state: UnsafeCell::new(buckets),
+            config: ArcSwap::from_pointee(config),
+            clock,
+            queue: Mutex::new(Queue {
+                sleep_counter: 0,
+                // SAFETY: we make sure to only interact with the same queue
+                queue: PinList::new(unsafe { pin_list::id::DebugChecked::new() }),
+                token: Some(RateToken),
+            }),
+        }
+    }
+
+    /// Atomically retunes the live limiter's rate and burst for every bucket
+    /// dimension, without dropping any bucket state. Unlike
+    /// [`RateLimiter::reconfigure`], `empty_at` is not clamped when a
+    /// `bucket_width` shrinks.
+    pub fn reconfigure(&self, new: MultiLeakyBucketConfig) {
+        self.config.store(Arc::new(new));
+    }
+
+    /// Charges every `(TokenType, count)` pair in `reqs` against its bucket,
+    /// queueing (fairly, FIFO) and sleeping until all of them have space.
+    /// Mirrors [`RateLimiter::acquire`]; see [`MultiLeakyBucketState::add_tokens`]
+    /// for the all-or-nothing commit semantics across buckets.
+    ///
+    /// Returns `true` if we had to throttle (wait) before the tokens were granted.
+    pub async fn acquire(&self, reqs: &[(TokenType, f64)]) -> bool {
+        let start = self.clock.now();
 
         let mut entry = std::pin::pin!(Queue::wait(&self.queue));
         let start_count = entry.as_mut().await;
@@ -293,10 +894,13 @@ impl RateLimiter {
             .expect("token should be init if we returned from enqueued");
 
         loop {
+            // Re-read the (possibly hot-reloaded) config each iteration.
+            let config = self.config.load();
+            let now = self.clock.now();
             // SAFETY: we are holding the token.
             let res = unsafe {
                 let state = &mut *self.state.get();
-                state.add_tokens(&self.config, start, count as f64)
+                state.add_tokens(&config, start, now, reqs)
             };
             match res {
                 Ok(()) => return start_count < *sleep_counter,
@@ -315,7 +919,10 @@ mod tests {
 
     use tokio::time::Instant;
 
-    use super::{LeakyBucketConfig, LeakyBucketState};
+    use super::{
+        KeyedRateLimiter, LeakyBucketConfig, LeakyBucketState, ManualClock, MultiLeakyBucketConfig,
+        MultiLeakyBucketState, MultiRateLimiter, RateLimiter, TokenType,
+    };
 
     #[tokio::test(start_paused = true)]
     async fn check() {
@@ -328,15 +935,16 @@ mod tests {
 
         let mut state = LeakyBucketState {
             empty_at: Instant::now(),
+            one_time_burst: 0.0,
         };
 
         // supports burst
         {
             // should work for 100 requests this instant
             for _ in 0..100 {
-                state.add_tokens(&config, Instant::now(), 1.0).unwrap();
+                state.add_tokens(&config, Instant::now(), Instant::now(), 1.0).unwrap();
             }
-            let ready = state.add_tokens(&config, Instant::now(), 1.0).unwrap_err();
+            let ready = state.add_tokens(&config, Instant::now(), Instant::now(), 1.0).unwrap_err();
             assert_eq!(ready - Instant::now(), Duration::from_millis(10));
         }
 
@@ -349,9 +957,9 @@ mod tests {
             // after 1s more, we should not over count the tokens and allow more than 200 requests.
             tokio::time::advance(Duration::from_secs(1)).await;
             for _ in 0..100 {
-                state.add_tokens(&config, Instant::now(), 1.0).unwrap();
+                state.add_tokens(&config, Instant::now(), Instant::now(), 1.0).unwrap();
             }
-            let ready = state.add_tokens(&config, Instant::now(), 1.0).unwrap_err();
+            let ready = state.add_tokens(&config, Instant::now(), Instant::now(), 1.0).unwrap_err();
             assert_eq!(ready - Instant::now(), Duration::from_millis(10));
         }
 
@@ -362,7 +970,7 @@ mod tests {
             // should sustain 100rps
             for _ in 0..2000 {
                 tokio::time::advance(Duration::from_millis(10)).await;
-                state.add_tokens(&config, Instant::now(), 1.0).unwrap();
+                state.add_tokens(&config, Instant::now(), Instant::now(), 1.0).unwrap();
             }
         }
 
@@ -377,19 +985,222 @@ mod tests {
             // but we already have 1s available, so we wait 1s from start.
             let start = Instant::now();
 
-            let ready = state.add_tokens(&config, start, 200.0).unwrap_err();
+            let ready = state.add_tokens(&config, start, Instant::now(), 200.0).unwrap_err();
             assert_eq!(ready - Instant::now(), Duration::from_secs(1));
 
             tokio::time::advance(Duration::from_millis(500)).await;
-            let ready = state.add_tokens(&config, start, 200.0).unwrap_err();
+            let ready = state.add_tokens(&config, start, Instant::now(), 200.0).unwrap_err();
             assert_eq!(ready - Instant::now(), Duration::from_millis(500));
 
             tokio::time::advance(Duration::from_millis(500)).await;
-            state.add_tokens(&config, start, 200.0).unwrap();
+            state.add_tokens(&config, start, Instant::now(), 200.0).unwrap();
 
             // bucket should be completely full now
-            let ready = state.add_tokens(&config, Instant::now(), 1.0).unwrap_err();
+            let ready = state.add_tokens(&config, Instant::now(), Instant::now(), 1.0).unwrap_err();
             assert_eq!(ready - Instant::now(), Duration::from_millis(10));
         }
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn multi_bucket_all_or_nothing() {
+        // bytes: 100B/s, burst 100B; ops: 10 ops/s, burst 10 ops.
+        let config = MultiLeakyBucketConfig::new([
+            LeakyBucketConfig {
+                cost: Duration::from_millis(10),
+                bucket_width: Duration::from_millis(1000),
+            },
+            LeakyBucketConfig {
+                cost: Duration::from_millis(100),
+                bucket_width: Duration::from_millis(1000),
+            },
+        ]);
+
+        let mut state = MultiLeakyBucketState::new([
+            LeakyBucketState {
+                empty_at: Instant::now(),
+                one_time_burst: 0.0,
+            },
+            LeakyBucketState {
+                empty_at: Instant::now(),
+                one_time_burst: 0.0,
+            },
+        ]);
+
+        let start = Instant::now();
+
+        // The ops bucket (10 burst) fills long before the bytes bucket (100
+        // burst). Charging 50 bytes + 10 ops succeeds, exhausting ops exactly.
+        state
+            .add_tokens(&config, start, Instant::now(), &[(TokenType::Bytes, 50.0), (TokenType::Ops, 10.0)])
+            .unwrap();
+
+        // One more op is blocked. Crucially the bytes bucket must not have been
+        // charged: the rejection leaves both buckets untouched.
+        let ready = state
+            .add_tokens(&config, start, Instant::now(), &[(TokenType::Bytes, 1.0), (TokenType::Ops, 1.0)])
+            .unwrap_err();
+        // ops refills at one every 100ms.
+        assert_eq!(ready - Instant::now(), Duration::from_millis(100));
+
+        // Since the failed charge committed nothing, the bytes bucket still has
+        // 50 of its 100 tokens free: an ops-only charge after the wait proceeds.
+        tokio::time::advance(Duration::from_millis(100)).await;
+        state
+            .add_tokens(&config, Instant::now(), Instant::now(), &[(TokenType::Ops, 1.0)])
+            .unwrap();
+
+        // Now fill the bytes bucket instead, so bytes is the only blocked
+        // dimension, and confirm its instant is the one returned.
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let now = Instant::now();
+        state
+            .add_tokens(&config, now, now, &[(TokenType::Bytes, 100.0), (TokenType::Ops, 1.0)])
+            .unwrap();
+        let ready = state
+            .add_tokens(&config, now, now, &[(TokenType::Bytes, 1.0), (TokenType::Ops, 1.0)])
+            .unwrap_err();
+        // bytes refills one every 10ms, ops one every 100ms; the bytes bucket is
+        // full so bytes is the binding constraint here.
+        assert_eq!(ready - Instant::now(), Duration::from_millis(10));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn multi_rate_limiter_queues_and_waits() {
+        // bytes: 100B/s, burst 100B; ops: 10 ops/s, burst 10 ops.
+        let config = MultiLeakyBucketConfig::new([
+            LeakyBucketConfig::new(100.0, 100.0),
+            LeakyBucketConfig::new(10.0, 10.0),
+        ]);
+        let buckets = MultiLeakyBucketState::new([
+            LeakyBucketState::with_initial_tokens(&config.configs[0], 0.0),
+            LeakyBucketState::with_initial_tokens(&config.configs[1], 0.0),
+        ]);
+        let limiter = MultiRateLimiter::new(config, buckets);
+
+        // Exhausts the ops burst; within budget so no throttling.
+        for _ in 0..10 {
+            assert!(!limiter.acquire(&[(TokenType::Ops, 1.0)]).await);
+        }
+
+        // Ops bucket is full: this call must sleep, and report that it did.
+        assert!(limiter.acquire(&[(TokenType::Ops, 1.0)]).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keyed_limiter_evicts_drained_keys() {
+        // 100rps, burst 100.
+        let limiter = KeyedRateLimiter::<u32>::new(LeakyBucketConfig::new(100.0, 100.0));
+
+        // Hammer many distinct one-shot keys with a single token each.
+        for key in 0..1000 {
+            limiter.add_tokens(key, Instant::now(), 1.0).unwrap();
+        }
+        assert_eq!(limiter.len(), 1000);
+
+        // A single token drains in `cost` = 10ms; a sweep before then keeps the
+        // still-filling buckets.
+        limiter.cleanup(Instant::now());
+        assert_eq!(limiter.len(), 1000);
+
+        // Once the drain window has fully elapsed, every bucket is empty and the
+        // map shrinks back to zero.
+        tokio::time::advance(Duration::from_millis(10)).await;
+        limiter.cleanup(Instant::now());
+        assert_eq!(limiter.len(), 0);
+
+        // Re-acquiring a swept key starts fresh: no rate was lost.
+        limiter.add_tokens(0, Instant::now(), 1.0).unwrap();
+        assert_eq!(limiter.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn try_acquire_and_timeout() {
+        // 100rps, burst 100, starting empty.
+        let limiter = RateLimiter::with_initial_tokens(LeakyBucketConfig::new(100.0, 100.0), 0.0);
+
+        // The burst is immediately available without blocking.
+        for _ in 0..100 {
+            limiter.try_acquire(1).unwrap();
+        }
+
+        // Bucket full: try_acquire fails fast with the refill instant, 10ms out.
+        let ready = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(ready - Instant::now(), Duration::from_millis(10));
+
+        // A deadline before that instant sheds the request rather than waiting.
+        let deadline = Instant::now() + Duration::from_millis(5);
+        assert!(!limiter.acquire_timeout(1, deadline).await);
+
+        // A generous deadline waits through the refill and succeeds.
+        let deadline = Instant::now() + Duration::from_secs(1);
+        assert!(limiter.acquire_timeout(1, deadline).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reconfigure_hot_reload() {
+        // 100rps, burst 100, starting empty.
+        let limiter = RateLimiter::with_initial_tokens(LeakyBucketConfig::new(100.0, 100.0), 0.0);
+
+        // Drain the full burst; next token is 10ms out at 100rps.
+        for _ in 0..100 {
+            limiter.try_acquire(1).unwrap();
+        }
+        let ready = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(ready - Instant::now(), Duration::from_millis(10));
+
+        // Retune to 1000rps while full. The narrower bucket width is clamped so
+        // the bucket isn't left instantly overfull, and the faster rate takes
+        // effect immediately: the next token is now only 1ms out.
+        limiter.reconfigure(LeakyBucketConfig::new(1000.0, 100.0));
+        assert_eq!(limiter.steady_rps(), 1000.0);
+        let ready = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(ready - Instant::now(), Duration::from_millis(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn one_time_burst_spent_once() {
+        // 100rps, burst 100 (width 1000ms), plus a one-time pool of 50 tokens.
+        let config = LeakyBucketConfig::new(100.0, 100.0);
+        let mut state = LeakyBucketState::with_one_time_burst(50.0);
+        let start = Instant::now();
+
+        // 50 tokens drawn entirely from the one-time pool: `empty_at` is never
+        // advanced, so the steady bucket is still empty afterwards.
+        state.add_tokens(&config, start, Instant::now(), 50.0).unwrap();
+        assert!(state.bucket_is_empty(Instant::now()));
+
+        // The pool is now exhausted. From here on the steady bucket behaves
+        // exactly as one that never had a burst: 100 tokens fill it, the 101st
+        // is 10ms out.
+        for _ in 0..100 {
+            state.add_tokens(&config, start, Instant::now(), 1.0).unwrap();
+        }
+        let ready = state.add_tokens(&config, start, Instant::now(), 1.0).unwrap_err();
+        assert_eq!(ready - Instant::now(), Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn manual_clock_drives_limiter() {
+        // A manually-driven clock lets us exercise the limiter deterministically
+        // without leaning on `tokio::time`'s paused-mode globals.
+        let clock = ManualClock::new(Instant::now());
+        // 100rps, burst 100, starting empty.
+        let limiter = RateLimiter::with_initial_tokens_and_clock(
+            LeakyBucketConfig::new(100.0, 100.0),
+            0.0,
+            clock.clone(),
+        );
+
+        // Drain the burst; the next token is 10ms out in the manual clock's
+        // frame, independent of wall-clock time.
+        for _ in 0..100 {
+            limiter.try_acquire(1).unwrap();
+        }
+        let ready = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(ready - clock.now(), Duration::from_millis(10));
+
+        // Advancing only the manual clock past the refill frees a token again.
+        clock.advance(Duration::from_millis(10));
+        limiter.try_acquire(1).unwrap();
+    }
 }
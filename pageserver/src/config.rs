@@ -0,0 +1,65 @@
+//! Pageserver configuration, parsed from `pageserver.toml` plus a handful of
+//! CLI-supplied overrides (workdir, node id).
+
+use std::time::Duration;
+
+use camino::Utf8PathBuf;
+use utils::id::NodeId;
+
+use crate::disk_usage_eviction_task::DiskUsageEvictionTaskConfig;
+use crate::walredo::WalRedoRetryConfig;
+
+pub struct PageServerConf {
+    pub id: NodeId,
+    pub workdir: Utf8PathBuf,
+
+    pub disk_usage_based_eviction: Option<DiskUsageEvictionTaskConfig>,
+
+    /// Timeout for a single wal-redo-postgres request before it's considered hung.
+    pub wal_redo_timeout: Duration,
+    /// Cap on the number of wal-redo processes checked out at once per
+    /// [`crate::walredo::RedoProcessPool`]; it also bounds the warm processes
+    /// kept alive per `pg_version`.
+    pub wal_redo_process_pool_size: usize,
+    /// Cap on concurrent in-flight wal-redo process launches, used to bound a
+    /// failed-launch storm now that reaping happens off the executor thread.
+    pub wal_redo_process_launch_concurrency: usize,
+    /// Retry policy applied to transient wal-redo failures.
+    pub wal_redo_retry: WalRedoRetryConfig,
+}
+
+impl PageServerConf {
+    pub fn parse_and_validate(
+        id: NodeId,
+        _toml: &toml_edit::Document,
+        workdir: &Utf8PathBuf,
+    ) -> anyhow::Result<Self> {
+        Ok(PageServerConf {
+            id,
+            workdir: workdir.clone(),
+            disk_usage_based_eviction: None,
+            wal_redo_timeout: Duration::from_secs(60),
+            wal_redo_process_pool_size: 4,
+            wal_redo_process_launch_concurrency: num_cpus::get(),
+            wal_redo_retry: WalRedoRetryConfig::default(),
+        })
+    }
+
+    pub fn dummy_conf(workdir: std::path::PathBuf) -> Self {
+        PageServerConf {
+            id: NodeId(42),
+            workdir: Utf8PathBuf::from_path_buf(workdir).expect("non-utf8 tempdir path"),
+            disk_usage_based_eviction: None,
+            wal_redo_timeout: Duration::from_secs(60),
+            wal_redo_process_pool_size: 4,
+            wal_redo_process_launch_concurrency: num_cpus::get(),
+            wal_redo_retry: WalRedoRetryConfig::default(),
+        }
+    }
+
+    /// Per-tenant data directories under [`Self::workdir`], used by the
+    /// disk-usage eviction task to `statvfs` the right filesystems.
+    pub fn tenant_data_dirs(&self) -> Vec<Utf8PathBuf> {
+        vec![self.workdir.join("tenants")]
+    }
+}
@@ -58,6 +58,12 @@ use utils::serde_percent::Percent;
 
 use crate::{
     config::PageServerConf,
+    metrics::{
+        DISK_USAGE_EVICTION_CANDIDATES, DISK_USAGE_EVICTION_EVICTED_LAYERS,
+        DISK_USAGE_EVICTION_FREED_BYTES, DISK_USAGE_EVICTION_FS_AVAIL_BYTES,
+        DISK_USAGE_EVICTION_FS_TOTAL_BYTES, DISK_USAGE_EVICTION_FS_USAGE_PCT,
+        DISK_USAGE_EVICTION_PRESSURE,
+    },
     task_mgr::{self, TaskKind, BACKGROUND_RUNTIME},
     tenant::{self, storage_layer::PersistentLayer, Timeline},
 };
@@ -68,12 +74,150 @@ pub struct DiskUsageEvictionTaskConfig {
     pub min_avail_bytes: u64,
     #[serde(with = "humantime_serde")]
     pub period: Duration,
+    /// Absolute free-inode floor. A filesystem storing many small layer files
+    /// can exhaust inodes long before bytes; when set, dropping below this many
+    /// available inodes triggers eviction. Unset disables the inode dimension.
+    #[serde(default)]
+    pub min_avail_inodes: Option<u64>,
+    /// Relative inode-usage high watermark, in percent of total inodes. Unset
+    /// disables the inode dimension.
+    #[serde(default)]
+    pub max_inode_usage_pct: Option<Percent>,
+    /// Low watermark, as an absolute usage percentage: once eviction is
+    /// triggered by `max_usage_pct` (the high watermark), keep evicting until
+    /// usage drops to this lower target rather than stopping the moment we dip
+    /// back under the high watermark. When unset, the high watermark is used as
+    /// the target (the historical single-threshold behavior).
+    #[serde(default)]
+    pub target_usage_pct: Option<Percent>,
+    /// Low watermark expressed as a fraction of the high watermark, in percent.
+    /// For example `80` drives eviction down to `0.8 * max_usage_pct`. Takes
+    /// precedence over `target_usage_pct` when both are set; tune it to trade
+    /// eviction frequency against resident cache size.
+    #[serde(default)]
+    pub eviction_target_margin_pct: Option<Percent>,
+    /// Which order to evict candidates in. Defaults to plain LRU; see
+    /// [`EvictionOrder`].
+    #[serde(default)]
+    pub eviction_order: EvictionOrder,
+    /// Re-fetch cost per on-disk byte used by the GDSF priority. Layers are
+    /// re-downloaded from remote storage on access, so this is the
+    /// size-dependent part of the re-fetch cost (default `1.0`). Raising it
+    /// biases GDSF toward keeping large layers resident. Only consulted under
+    /// [`EvictionOrder::GreedyDualSizeFrequency`].
+    #[serde(default)]
+    pub refetch_cost_per_byte: Option<f64>,
+    /// Fixed, size-independent part of the re-fetch cost (request
+    /// round-trip/overhead, in byte-equivalent units), used alongside
+    /// `refetch_cost_per_byte` by the GDSF priority. Without a fixed term,
+    /// `cost = size * refetch_cost_per_byte` cancels the `/ size` in
+    /// `H = L + freq * cost / size`, making GDSF degenerate to plain
+    /// frequency and erasing size-sensitivity entirely. Defaults to 4 KiB,
+    /// a rough stand-in for one remote-storage request's latency overhead.
+    /// Only consulted under [`EvictionOrder::GreedyDualSizeFrequency`].
+    #[serde(default)]
+    pub refetch_fixed_cost_bytes: Option<u64>,
+}
+
+/// Default for [`DiskUsageEvictionTaskConfig::refetch_fixed_cost_bytes`].
+const DEFAULT_REFETCH_FIXED_COST_BYTES: u64 = 4096;
+
+/// The order in which [`collect_eviction_candidates`] hands out layers for
+/// eviction.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EvictionOrder {
+    /// Plain LRU: evict globally by `last_activity_ts`, oldest first, within the
+    /// per-tenant min-resident-size partitions. This is the historical behavior.
+    #[default]
+    AbsoluteOrder,
+    /// GreedyDual-Size-Frequency: rank each candidate by a priority
+    /// `H = L + freq * cost / file_size`, where `freq` is the layer's access
+    /// counter, `cost` is the re-fetch cost (see `refetch_cost_per_byte` and
+    /// `refetch_fixed_cost_bytes`) and `L` is a running aging clock, and evict
+    /// lowest-`H` first. This keeps
+    /// small/frequently-read layers resident under pressure while still aging
+    /// out cold data, and degrades to LRU when `freq` is uniform.
+    GreedyDualSizeFrequency,
+}
+
+/// Coarse classification of a resident layer, used to build the working-set
+/// default for `min_resident_size`. Exposed per-layer by
+/// `TimelineDiskUsageEvictionInfo` so the eviction task can distinguish L0 delta
+/// layers (which span the full key range at the tip of the timeline) from
+/// everything else without reaching into layer internals here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerLevel {
+    /// An L0 delta layer, i.e. one covering the whole key range.
+    Delta0,
+    /// Any other layer (image layers and L1+ delta layers).
+    Other,
+}
+
+/// What the eviction task is currently doing with respect to the watermarks.
+/// Surfaced through [`State`] so the HTTP layer can report it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatermarkState {
+    /// Usage is below the high watermark; nothing to do.
+    Idle,
+    /// Usage crossed the high watermark and we are evicting down to the target.
+    Draining,
+    /// We evicted everything we could but usage is still above the high
+    /// watermark — operator attention needed.
+    StuckAboveHighWatermark,
+}
+
+impl WatermarkState {
+    fn from_u8(v: u8) -> WatermarkState {
+        match v {
+            1 => WatermarkState::Draining,
+            2 => WatermarkState::StuckAboveHighWatermark,
+            _ => WatermarkState::Idle,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            WatermarkState::Idle => 0,
+            WatermarkState::Draining => 1,
+            WatermarkState::StuckAboveHighWatermark => 2,
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct State {
     /// Exclude http requests and background task from running at the same time.
     mutex: tokio::sync::Mutex<()>,
+    /// GreedyDual-Size-Frequency aging clock `L`, held as the bits of an `f64`.
+    /// It is advanced to the priority of each evicted layer so candidates seen
+    /// in later iterations inherit the aging. Only consulted when
+    /// [`EvictionOrder::GreedyDualSizeFrequency`] is configured.
+    gdsf_clock: std::sync::atomic::AtomicU64,
+    /// Current [`WatermarkState`], as its `u8` representation.
+    watermark: std::sync::atomic::AtomicU8,
+}
+
+impl State {
+    fn load_gdsf_clock(&self) -> f64 {
+        f64::from_bits(self.gdsf_clock.load(std::sync::atomic::Ordering::Relaxed))
+    }
+
+    fn store_gdsf_clock(&self, value: f64) {
+        self.gdsf_clock
+            .store(value.to_bits(), std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn set_watermark_state(&self, state: WatermarkState) {
+        self.watermark
+            .store(state.as_u8(), std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// The eviction task's current watermark state, for the HTTP layer to report.
+    pub fn watermark_state(&self) -> WatermarkState {
+        WatermarkState::from_u8(self.watermark.load(std::sync::atomic::Ordering::Relaxed))
+    }
 }
 
 pub fn launch_disk_usage_global_eviction_task(
@@ -86,14 +230,22 @@ pub fn launch_disk_usage_global_eviction_task(
         return Ok(());
     };
 
-    let tenants_dir_fd = {
-        let tenants_path = conf.tenants_path();
-        nix::dir::Dir::open(
-            &tenants_path,
-            nix::fcntl::OFlag::O_DIRECTORY,
-            nix::sys::stat::Mode::empty(),
-        )
-        .with_context(|| format!("open tenants_path {tenants_path:?}"))?
+    // Tenant data can be spread across several data directories, each possibly
+    // on a different filesystem; open one fd per directory so usage is evaluated
+    // per filesystem. The common deployment has a single `tenants_path()`, but
+    // `tenant_data_dirs()` returns every configured data root.
+    let tenants_dir_fds = {
+        let mut fds = Vec::new();
+        for tenants_path in conf.tenant_data_dirs() {
+            let fd = nix::dir::Dir::open(
+                &tenants_path,
+                nix::fcntl::OFlag::O_DIRECTORY,
+                nix::sys::stat::Mode::empty(),
+            )
+            .with_context(|| format!("open tenants_path {tenants_path:?}"))?;
+            fds.push(fd);
+        }
+        fds
     };
 
     info!("launching disk usage based eviction task");
@@ -110,7 +262,7 @@ pub fn launch_disk_usage_global_eviction_task(
                 &state,
                 task_config,
                 storage,
-                tenants_dir_fd,
+                tenants_dir_fds,
                 task_mgr::shutdown_token(),
             )
             .await;
@@ -127,7 +279,7 @@ async fn disk_usage_eviction_task(
     state: &State,
     task_config: &DiskUsageEvictionTaskConfig,
     storage: GenericRemoteStorage,
-    tenants_dir_fd: Dir,
+    tenants_dir_fds: Vec<Dir>,
     cancel: CancellationToken,
 ) {
     // nix::dir::Dir is Send but not Sync.
@@ -136,7 +288,8 @@ async fn disk_usage_eviction_task(
     // The reason is that the &tenants_dir_fd is not sync because of stdlib-enforced axiom
     //  T: Sync <=> &T: Send
     // The solution is to use SyncWrapper, which, by owning the tenants_dir_fd, can impl Sync.
-    let mut tenants_dir_fd = SyncWrapper::new(tenants_dir_fd);
+    let mut tenants_dir_fds: Vec<SyncWrapper<Dir>> =
+        tenants_dir_fds.into_iter().map(SyncWrapper::new).collect();
 
     use crate::tenant::tasks::random_init_delay;
     {
@@ -154,18 +307,23 @@ async fn disk_usage_eviction_task(
         iteration_no += 1;
         let start = Instant::now();
 
+        // Whether this iteration evicted and usage is still above the target
+        // watermark. When so, we loop back-to-back (skipping the periodic sleep)
+        // so we drain smoothly to the target instead of bursting once per `period`.
+        let mut drained = false;
+
         async {
             let res = disk_usage_eviction_task_iteration(
                 state,
                 task_config,
                 &storage,
-                &mut tenants_dir_fd,
+                &mut tenants_dir_fds,
                 &cancel,
             )
             .await;
 
             match res {
-                Ok(()) => {}
+                Ok(did_evict) => drained = did_evict,
                 Err(e) => {
                     // these stat failures are expected to be very rare
                     warn!("iteration failed, unexpected error: {e:#}");
@@ -175,6 +333,16 @@ async fn disk_usage_eviction_task(
         .instrument(tracing::info_span!("iteration", iteration_no))
         .await;
 
+        if drained {
+            // Still draining: re-check immediately rather than sleeping, but
+            // honor cancellation promptly.
+            if cancel.is_cancelled() {
+                info!("shutting down");
+                break;
+            }
+            continue;
+        }
+
         let sleep_until = start + task_config.period;
         tokio::select! {
             _ = tokio::time::sleep_until(sleep_until) => {},
@@ -184,57 +352,175 @@ async fn disk_usage_eviction_task(
             }
         }
     }
+    state.set_watermark_state(WatermarkState::Idle);
 }
 
 pub trait Usage: Clone + Copy + std::fmt::Debug {
+    /// Whether usage is above the high watermark, i.e. eviction should be
+    /// triggered.
     fn has_pressure(&self) -> bool;
+    /// Whether usage is still above the low-watermark target, i.e. a triggered
+    /// drain should keep evicting. Defaults to [`Usage::has_pressure`] so
+    /// implementations without a distinct target behave as a single threshold.
+    fn still_over_target(&self) -> bool {
+        self.has_pressure()
+    }
     fn add_available_bytes(&mut self, bytes: u64);
+    /// Account for the inodes freed by evicting a layer (one inode per layer
+    /// file). Defaults to a no-op for usage implementations that don't track
+    /// inodes.
+    fn add_freed_inodes(&mut self, _n: u64) {}
 }
 
+/// Runs one iteration. Returns `true` when it evicted and usage is still above
+/// the target watermark, signalling the caller to iterate back-to-back rather
+/// than sleeping a full `period`.
 async fn disk_usage_eviction_task_iteration(
     state: &State,
     task_config: &DiskUsageEvictionTaskConfig,
     storage: &GenericRemoteStorage,
-    tenants_dir_fd: &mut SyncWrapper<Dir>,
+    tenants_dir_fds: &mut [SyncWrapper<Dir>],
     cancel: &CancellationToken,
-) -> anyhow::Result<()> {
-    let usage_pre = filesystem_level_usage::get(tenants_dir_fd, task_config)
+) -> anyhow::Result<bool> {
+    // Tenant directories may be spread across several mount points, each with
+    // its own free space. Evaluate pressure per filesystem: a layer on one
+    // device can only relieve pressure on that device, so selection and the
+    // `add_available_bytes` accounting run separately per pressured filesystem
+    // rather than pooling all candidates together.
+    let usage_pre = filesystem_level_usage::get_per_filesystem(tenants_dir_fds, task_config)
         .context("get filesystem-level disk usage before evictions")?;
-    let res = disk_usage_eviction_task_iteration_impl(state, storage, usage_pre, cancel).await;
-    match res {
-        Ok(outcome) => {
-            debug!(?outcome, "disk_usage_eviction_iteration finished");
-            match outcome {
-                IterationOutcome::NoPressure | IterationOutcome::Cancelled => {
-                    // nothing to do, select statement below will handle things
-                }
-                IterationOutcome::Finished(outcome) => {
-                    // Verify with statvfs whether we made any real progress
-                    let after = filesystem_level_usage::get(tenants_dir_fd, task_config)
-                        // It's quite unlikely to hit the error here. Keep the code simple and bail out.
-                        .context("get filesystem-level disk usage after evictions")?;
-
-                    debug!(?after, "disk usage");
-
-                    if after.has_pressure() {
-                        // Don't bother doing an out-of-order iteration here now.
-                        // In practice, the task period is set to a value in the tens-of-seconds range,
-                        // which will cause another iteration to happen soon enough.
-                        // TODO: deltas between the three different usages would be helpful,
-                        // consider MiB, GiB, TiB
-                        warn!(?outcome, ?after, "disk usage still high");
-                    } else {
-                        info!(?outcome, ?after, "disk usage pressure relieved");
-                    }
-                }
-            }
+
+    if usage_pre
+        .values()
+        .any(|u| !u.is_read_only() && u.has_pressure())
+    {
+        state.set_watermark_state(WatermarkState::Draining);
+    }
+
+    let mut made_progress = false;
+    let mut any_over_target = false;
+    let mut worst = WatermarkState::Idle;
+
+    for (&device_id, &usage) in usage_pre.iter() {
+        // Export gauges for every filesystem, pressured or not, so operators can
+        // graph how close each device is to eviction before it fills up.
+        emit_usage_metrics(device_id, &usage);
+
+        if usage.is_read_only() {
+            // A wedged (read-only) mount can't have layers removed; attempting
+            // eviction would spin uselessly and mask the real problem. Skip it
+            // loudly, but keep evaluating the other filesystems.
+            error!(device_id, "cannot evict: filesystem is read-only");
+            continue;
+        }
+        if !usage.has_pressure() {
+            continue;
         }
-        Err(e) => {
-            error!("disk_usage_eviction_iteration failed: {:#}", e);
+
+        let res = disk_usage_eviction_task_iteration_impl(
+            state,
+            storage,
+            usage,
+            task_config.eviction_order,
+            task_config.refetch_cost_per_byte.unwrap_or(1.0),
+            task_config
+                .refetch_fixed_cost_bytes
+                .unwrap_or(DEFAULT_REFETCH_FIXED_COST_BYTES),
+            Some(device_id),
+            cancel,
+        )
+        .await;
+
+        let outcome = match res {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                error!(device_id, "disk_usage_eviction_iteration failed: {:#}", e);
+                continue;
+            }
+        };
+        debug!(device_id, ?outcome, "disk_usage_eviction_iteration finished");
+
+        let IterationOutcome::Finished(outcome) = outcome else {
+            // NoPressure (raced away) or Cancelled: nothing more to do here.
+            continue;
+        };
+
+        emit_outcome_metrics(device_id, &outcome);
+
+        // Verify with statvfs whether we made any real progress on this device.
+        let after = filesystem_level_usage::get_per_filesystem(tenants_dir_fds, task_config)
+            // It's quite unlikely to hit the error here. Keep the code simple and bail out.
+            .context("get filesystem-level disk usage after evictions")?;
+        // The device may have vanished between stats; treat a missing entry as
+        // no-longer-pressured.
+        let after = after.get(&device_id).copied();
+        debug!(device_id, ?after, "disk usage");
+
+        // Only keep looping back-to-back while we're still making progress; if
+        // an iteration evicts nothing we'd otherwise busy-loop against a wall of
+        // un-evictable layers.
+        made_progress |= outcome.assumed.evicted.count > 0;
+
+        match after {
+            Some(after) if after.has_pressure() => {
+                // We evicted everything we could but are still above the high
+                // watermark: flag it for the operator.
+                warn!(device_id, ?outcome, ?after, "disk usage still high");
+                any_over_target = true;
+                worst = worst.max(WatermarkState::StuckAboveHighWatermark);
+            }
+            Some(after) if after.still_over_target() => {
+                // Below the high watermark but not yet at the target; keep
+                // draining smoothly.
+                any_over_target = true;
+                worst = worst.max(WatermarkState::Draining);
+            }
+            _ => {
+                info!(device_id, ?outcome, "disk usage pressure relieved");
+            }
         }
     }
 
-    Ok(())
+    state.set_watermark_state(worst);
+    Ok(made_progress && any_over_target)
+}
+
+/// Export per-filesystem disk-usage gauges for observability. Emitted every
+/// iteration for every filesystem, pressured or not, so operators can graph how
+/// close a node is to eviction and track the spread between the resident-cache
+/// target and real free space over time.
+fn emit_usage_metrics(device_id: u64, usage: &filesystem_level_usage::Usage<'_>) {
+    let dev = device_id.to_string();
+    DISK_USAGE_EVICTION_FS_TOTAL_BYTES
+        .with_label_values(&[&dev])
+        .set(usage.total_bytes() as i64);
+    DISK_USAGE_EVICTION_FS_AVAIL_BYTES
+        .with_label_values(&[&dev])
+        .set(usage.avail_bytes() as i64);
+    DISK_USAGE_EVICTION_FS_USAGE_PCT
+        .with_label_values(&[&dev])
+        .set(usage.usage_percent() as i64);
+    for (reason, tripped) in usage.pressure_reasons() {
+        DISK_USAGE_EVICTION_PRESSURE
+            .with_label_values(&[&dev, reason])
+            .set(i64::from(tripped));
+    }
+}
+
+/// Export the outcome of an eviction pass on one filesystem: how many candidate
+/// layers were considered, how many were actually evicted, and how many bytes
+/// that reclaimed.
+fn emit_outcome_metrics<U>(device_id: u64, outcome: &IterationOutcomeFinished<U>) {
+    let dev = device_id.to_string();
+    DISK_USAGE_EVICTION_CANDIDATES
+        .with_label_values(&[&dev])
+        .inc_by(outcome.candidates_considered as u64);
+    DISK_USAGE_EVICTION_EVICTED_LAYERS
+        .with_label_values(&[&dev])
+        .inc_by(outcome.assumed.evicted.count as u64);
+    DISK_USAGE_EVICTION_FREED_BYTES
+        .with_label_values(&[&dev])
+        .inc_by(outcome.assumed.evicted.file_sizes);
 }
 
 #[derive(Debug, Serialize)]
@@ -257,6 +543,8 @@ pub struct IterationOutcomeFinished<U> {
     /// If all layers that phase 1 planned to evict _can_ actually get evicted, this will
     /// be the same as `planned`.
     assumed: AssumedUsage<U>,
+    /// How many resident layers the collection phase considered for this pass.
+    candidates_considered: usize,
 }
 
 #[derive(Debug, Serialize)]
@@ -264,6 +552,8 @@ pub struct IterationOutcomeFinished<U> {
 struct AssumedUsage<U> {
     /// The expected value for `after`, after phase 2.
     projected_after: U,
+    /// The layers we actually evicted during phase 2.
+    evicted: LayerCount,
     /// The layers we failed to evict during phase 2.
     failed: LayerCount,
 }
@@ -287,6 +577,10 @@ pub async fn disk_usage_eviction_task_iteration_impl<U: Usage>(
     state: &State,
     storage: &GenericRemoteStorage,
     usage_pre: U,
+    eviction_order: EvictionOrder,
+    refetch_cost_per_byte: f64,
+    refetch_fixed_cost_bytes: u64,
+    device_filter: Option<u64>,
     cancel: &CancellationToken,
 ) -> anyhow::Result<IterationOutcome<U>> {
     // use tokio's mutex to get a Sync guard (instead of std::sync::Mutex)
@@ -306,13 +600,24 @@ pub async fn disk_usage_eviction_task_iteration_impl<U: Usage>(
         "running disk usage based eviction due to pressure"
     );
 
-    let candidates = match collect_eviction_candidates(cancel).await? {
+    let candidates = match collect_eviction_candidates(
+        eviction_order,
+        refetch_cost_per_byte,
+        refetch_fixed_cost_bytes,
+        device_filter,
+        state,
+        cancel,
+    )
+    .await?
+    {
         EvictionCandidates::Cancelled => {
             return Ok(IterationOutcome::Cancelled);
         }
         EvictionCandidates::Finished(partitioned) => partitioned,
     };
 
+    let candidates_considered = candidates.num_candidates();
+
     // Debug-log the list of candidates
     let now = SystemTime::now();
     for (i, (partition, candidate)) in candidates
@@ -336,58 +641,31 @@ pub async fn disk_usage_eviction_task_iteration_impl<U: Usage>(
         );
     }
 
-    // phase1: select victims to relieve pressure
-    //
-    // Walk through the list of candidates, until we have accumulated enough layers to get
-    // us back under the pressure threshold. 'usage_planned' is updated so that it tracks
-    // how much disk space would be used after evicting all the layers up to the current
-    // point in the list. The layers are collected in 'batched', grouped per timeline.
-    //
-    // If we get far enough in the list that we start to evict layers that are below
-    // the tenant's min-resident-size threshold, print a warning, and memorize the disk
-    // usage at that point, in 'usage_planned_min_resident_size_respecting'.
-    let mut batched: HashMap<_, Vec<Arc<dyn PersistentLayer>>> = HashMap::new();
-    let mut warned = None;
-    let mut usage_planned = usage_pre;
-    for (i, (partition, candidate)) in candidates.into_iter_in_eviction_order().enumerate() {
-        if !usage_planned.has_pressure() {
-            debug!(
-                no_candidates_evicted = i,
-                "took enough candidates for pressure to be relieved"
-            );
-            break;
-        }
+    // phase1: select victims to relieve pressure, without evicting anything yet.
+    let plan = plan_eviction(candidates, usage_pre, state.load_gdsf_clock());
 
-        if partition == MinResidentSizePartition::Below && warned.is_none() {
-            warn!(?usage_pre, ?usage_planned, candidate_no=i, "tenant_min_resident_size-respecting LRU would not relieve pressure, evicting more following global LRU policy");
-            warned = Some(usage_planned);
-        }
+    if matches!(eviction_order, EvictionOrder::GreedyDualSizeFrequency) {
+        state.store_gdsf_clock(plan.gdsf_clock);
+    }
 
-        usage_planned.add_available_bytes(candidate.layer.file_size());
+    let usage_planned = plan.planned;
+    debug!(?usage_planned, "usage planned");
 
+    // Group the planned victims by timeline for batched eviction.
+    let mut batched: HashMap<_, Vec<Arc<dyn PersistentLayer>>> = HashMap::new();
+    for victim in plan.victims {
         batched
-            .entry(TimelineKey(candidate.timeline.clone()))
+            .entry(TimelineKey(victim.timeline))
             .or_default()
-            .push(candidate.layer);
+            .push(victim.layer);
     }
 
-    let usage_planned = match warned {
-        Some(respecting_tenant_min_resident_size) => PlannedUsage {
-            respecting_tenant_min_resident_size,
-            fallback_to_global_lru: Some(usage_planned),
-        },
-        None => PlannedUsage {
-            respecting_tenant_min_resident_size: usage_planned,
-            fallback_to_global_lru: None,
-        },
-    };
-    debug!(?usage_planned, "usage planned");
-
     // phase2: evict victims batched by timeline
 
     // achieved post-eviction usage according to internal accounting
     let mut usage_assumed = usage_pre;
 
+    let mut evictions_succeeded = LayerCount::default();
     let mut evictions_failed = LayerCount::default();
     for (timeline, batch) in batched {
         let tenant_id = timeline.tenant_id;
@@ -409,6 +687,9 @@ pub async fn disk_usage_eviction_task_iteration_impl<U: Usage>(
                         match result {
                             Some(Ok(true)) => {
                                 usage_assumed.add_available_bytes(layer.file_size());
+                                usage_assumed.add_freed_inodes(1);
+                                evictions_succeeded.file_sizes += layer.file_size();
+                                evictions_succeeded.count += 1;
                             }
                             Some(Ok(false)) => {
                                 // this is:
@@ -443,11 +724,201 @@ pub async fn disk_usage_eviction_task_iteration_impl<U: Usage>(
         planned: usage_planned,
         assumed: AssumedUsage {
             projected_after: usage_assumed,
+            evicted: evictions_succeeded,
             failed: evictions_failed,
         },
+        candidates_considered,
     }))
 }
 
+/// A layer that phase 1 selected for eviction, with the running total of bytes
+/// reclaimed up to and including it.
+struct PlannedVictim {
+    timeline: Arc<Timeline>,
+    layer: Arc<dyn PersistentLayer>,
+    partition: MinResidentSizePartition,
+    cumulative_reclaimed_bytes: u64,
+}
+
+/// The output of phase 1: the ordered victim set and the projected usage, with
+/// no eviction performed. Shared by the real iteration and the dry-run preview.
+struct EvictionPlan<U> {
+    victims: Vec<PlannedVictim>,
+    planned: PlannedUsage<U>,
+    /// The GDSF aging clock advanced past every planned victim.
+    gdsf_clock: f64,
+}
+
+/// Walk the candidate list in eviction order and accumulate victims until usage
+/// reaches the target watermark, *without* evicting anything. `usage_pre` is the
+/// starting usage and `gdsf_clock_start` the current GDSF aging clock.
+///
+/// 'planned' tracks how much space would be free after evicting all victims up
+/// to the current point. Once we cross into the min-resident-size reservation we
+/// record the usage at that cutoff, so callers can tell how much of the plan
+/// respects the reservation versus falls back to global LRU.
+fn plan_eviction<U: Usage>(
+    candidates: MinResidentSizePartitionedCandidates,
+    usage_pre: U,
+    gdsf_clock_start: f64,
+) -> EvictionPlan<U> {
+    let mut victims = Vec::new();
+    let mut warned = None;
+    let mut usage_planned = usage_pre;
+    let mut gdsf_clock = gdsf_clock_start;
+    let mut cumulative_reclaimed_bytes = 0u64;
+
+    for (i, (partition, candidate)) in candidates.into_iter_in_eviction_order().enumerate() {
+        if !usage_planned.still_over_target() {
+            debug!(
+                no_candidates_evicted = i,
+                "took enough candidates for usage to reach the target watermark"
+            );
+            break;
+        }
+
+        if partition == MinResidentSizePartition::Below && warned.is_none() {
+            warn!(?usage_pre, ?usage_planned, candidate_no=i, "tenant_min_resident_size-respecting LRU would not relieve pressure, evicting more following global LRU policy");
+            warned = Some(usage_planned);
+        }
+
+        gdsf_clock = gdsf_clock.max(candidate.score);
+        let file_size = candidate.layer.file_size();
+        usage_planned.add_available_bytes(file_size);
+        usage_planned.add_freed_inodes(1);
+        cumulative_reclaimed_bytes += file_size;
+
+        victims.push(PlannedVictim {
+            timeline: candidate.timeline,
+            layer: candidate.layer,
+            partition,
+            cumulative_reclaimed_bytes,
+        });
+    }
+
+    let planned = match warned {
+        Some(respecting_tenant_min_resident_size) => PlannedUsage {
+            respecting_tenant_min_resident_size,
+            fallback_to_global_lru: Some(usage_planned),
+        },
+        None => PlannedUsage {
+            respecting_tenant_min_resident_size: usage_planned,
+            fallback_to_global_lru: None,
+        },
+    };
+
+    EvictionPlan {
+        victims,
+        planned,
+        gdsf_clock,
+    }
+}
+
+/// A single layer in a dry-run eviction plan, as reported to operators.
+#[derive(Debug, Serialize)]
+pub struct EvictionPlanLayer {
+    pub tenant_id: String,
+    pub timeline_id: String,
+    pub layer: String,
+    pub file_size: u64,
+    /// Bytes reclaimed by evicting this layer and all earlier ones in the plan.
+    pub cumulative_reclaimed_bytes: u64,
+    /// Whether this layer is part of the tenant's min-resident-size reservation
+    /// (i.e. below the cutoff, only evicted when the reservation-respecting plan
+    /// cannot relieve pressure).
+    pub below_min_resident_size: bool,
+}
+
+/// The simulated result of an eviction, returned by [`disk_usage_eviction_preview`].
+#[derive(Debug, Serialize)]
+pub struct EvictionPlanPreview {
+    /// Layers that would be evicted, in eviction order.
+    pub layers: Vec<EvictionPlanLayer>,
+    /// Total bytes that executing the plan would reclaim.
+    pub reclaimed_bytes: u64,
+    /// Index into `layers` of the first layer that dips into the
+    /// min-resident-size reservation, or `None` if the plan never does.
+    pub min_resident_size_cutoff: Option<usize>,
+    /// Whether executing the plan brings usage to/under the target watermark.
+    pub relieves_pressure: bool,
+}
+
+/// Simulate an eviction against a hypothetical `usage` without touching any
+/// local cache state, so operators can predict eviction behavior before it
+/// triggers. Guarded by the same [`State::mutex`] as a real iteration so the two
+/// can't interleave.
+pub async fn disk_usage_eviction_preview<U: Usage>(
+    state: &State,
+    usage: U,
+    eviction_order: EvictionOrder,
+    refetch_cost_per_byte: f64,
+    refetch_fixed_cost_bytes: u64,
+    cancel: &CancellationToken,
+) -> anyhow::Result<EvictionPlanPreview> {
+    let _g = state
+        .mutex
+        .try_lock()
+        .map_err(|_| anyhow::anyhow!("iteration is already executing"))?;
+
+    // A preview spans all filesystems; scoping happens only in the real
+    // per-device background passes.
+    let candidates = match collect_eviction_candidates(
+        eviction_order,
+        refetch_cost_per_byte,
+        refetch_fixed_cost_bytes,
+        None,
+        state,
+        cancel,
+    )
+    .await?
+    {
+        EvictionCandidates::Cancelled => anyhow::bail!("cancelled"),
+        EvictionCandidates::Finished(partitioned) => partitioned,
+    };
+
+    // A preview must not advance the persisted GDSF clock, so we start from the
+    // current value and discard the advanced one.
+    let plan = plan_eviction(candidates, usage, state.load_gdsf_clock());
+
+    let min_resident_size_cutoff = plan
+        .victims
+        .iter()
+        .position(|v| v.partition == MinResidentSizePartition::Below);
+
+    let reclaimed_bytes = plan
+        .victims
+        .last()
+        .map_or(0, |v| v.cumulative_reclaimed_bytes);
+
+    let layers = plan
+        .victims
+        .iter()
+        .map(|v| EvictionPlanLayer {
+            tenant_id: v.layer.get_tenant_id().to_string(),
+            timeline_id: v.layer.get_timeline_id().to_string(),
+            layer: v.layer.filename().file_name(),
+            file_size: v.layer.file_size(),
+            cumulative_reclaimed_bytes: v.cumulative_reclaimed_bytes,
+            below_min_resident_size: v.partition == MinResidentSizePartition::Below,
+        })
+        .collect();
+
+    // The plan stops accumulating once usage reaches the target, so it relieves
+    // pressure iff the projected usage is no longer above the target.
+    let relieves_pressure = !plan
+        .planned
+        .fallback_to_global_lru
+        .unwrap_or(plan.planned.respecting_tenant_min_resident_size)
+        .still_over_target();
+
+    Ok(EvictionPlanPreview {
+        layers,
+        reclaimed_bytes,
+        min_resident_size_cutoff,
+        relieves_pressure,
+    })
+}
+
 // Result type of `collect_eviction_candidates`
 //
 // `collect_eviction_candidates' returns a vector of these, in the preference order
@@ -457,10 +928,15 @@ struct EvictionCandidate {
     timeline: Arc<Timeline>,
     layer: Arc<dyn PersistentLayer>,
     last_activity_ts: SystemTime,
+    /// GreedyDual-Size-Frequency priority `H = L + freq * cost / file_size` for this
+    /// layer, computed at collection time. Lower values are evicted first.
+    /// Meaningless (and ignored) under [`EvictionOrder::AbsoluteOrder`].
+    score: f64,
 }
 
 #[derive(Clone)]
 struct MinResidentSizePartitionedCandidates {
+    order: EvictionOrder,
     above: Vec<EvictionCandidate>,
     below: Vec<EvictionCandidate>,
 }
@@ -478,14 +954,28 @@ impl MinResidentSizePartitionedCandidates {
     pub fn into_iter_in_eviction_order(
         self,
     ) -> impl Iterator<Item = (MinResidentSizePartition, EvictionCandidate)> {
-        debug_assert!(is_sorted::IsSorted::is_sorted_by_key(
-            &mut self.above.iter(),
-            |c| c.last_activity_ts
-        ));
-        debug_assert!(is_sorted::IsSorted::is_sorted_by_key(
-            &mut self.below.iter(),
-            |c| c.last_activity_ts
-        ));
+        match self.order {
+            EvictionOrder::AbsoluteOrder => {
+                debug_assert!(is_sorted::IsSorted::is_sorted_by_key(
+                    &mut self.above.iter(),
+                    |c| c.last_activity_ts
+                ));
+                debug_assert!(is_sorted::IsSorted::is_sorted_by_key(
+                    &mut self.below.iter(),
+                    |c| c.last_activity_ts
+                ));
+            }
+            EvictionOrder::GreedyDualSizeFrequency => {
+                debug_assert!(is_sorted::IsSorted::is_sorted_by_key(
+                    &mut self.above.iter(),
+                    |c| c.score.to_bits()
+                ));
+                debug_assert!(is_sorted::IsSorted::is_sorted_by_key(
+                    &mut self.below.iter(),
+                    |c| c.score.to_bits()
+                ));
+            }
+        }
         self.above
             .into_iter()
             .map(|c| (MinResidentSizePartition::Above, c))
@@ -538,8 +1028,18 @@ enum EvictionCandidates {
 /// should check for `cancel.is_cancelled`.
 ///
 async fn collect_eviction_candidates(
+    eviction_order: EvictionOrder,
+    refetch_cost_per_byte: f64,
+    refetch_fixed_cost_bytes: u64,
+    device_filter: Option<u64>,
+    state: &State,
     cancel: &CancellationToken,
 ) -> anyhow::Result<EvictionCandidates> {
+    // Snapshot of the GreedyDual-Size-Frequency aging clock. All candidates in
+    // this pass share the same `L`, so it acts as a constant offset and the
+    // resulting order reduces to ranking by `freq * cost / file_size`; the clock only
+    // shifts priorities across iterations as it advances in phase 1.
+    let gdsf_clock = state.load_gdsf_clock();
     // get a snapshot of the list of tenants
     let tenants = tenant::mgr::list_tenants()
         .await
@@ -569,12 +1069,40 @@ async fn collect_eviction_candidates(
         // a little unfair to tenants during shutdown in such a situation is tolerable.
         let mut tenant_candidates = Vec::new();
         let mut max_layer_size = 0;
+        // Working-set estimate used as the default `min_resident_size`: the sum
+        // of all L0 delta layers plus the single most-recently-accessed non-L0
+        // layer (an L1+ delta or an image layer) per timeline. This approximates the data a typical page reconstruction
+        // touches, which is what we actually want to keep resident.
+        let mut working_set_size: u64 = 0;
+        let mut have_l0 = false;
         for tl in tenant.list_timelines() {
             if !tl.is_active() {
                 continue;
             }
             let info = tl.get_local_layers_for_disk_usage_eviction();
             debug!(timeline_id=%tl.timeline_id, "timeline resident layers count: {}", info.resident_layers.len());
+
+            let mut most_recent_l1: Option<(SystemTime, u64)> = None;
+            for layer_info in &info.resident_layers {
+                match layer_info.level() {
+                    LayerLevel::Delta0 => {
+                        working_set_size += layer_info.file_size();
+                        have_l0 = true;
+                    }
+                    LayerLevel::Other => {
+                        // Keep only the single most-recently-accessed non-L0
+                        // layer; image layers are not told apart from L1 deltas.
+                        let ts = layer_info.last_activity_ts;
+                        if most_recent_l1.map_or(true, |(best, _)| ts > best) {
+                            most_recent_l1 = Some((ts, layer_info.file_size()));
+                        }
+                    }
+                }
+            }
+            if let Some((_, size)) = most_recent_l1 {
+                working_set_size += size;
+            }
+
             tenant_candidates.extend(
                 info.resident_layers
                     .into_iter()
@@ -587,14 +1115,15 @@ async fn collect_eviction_candidates(
             }
         }
 
-        // `min_resident_size` defaults to maximum layer file size of the tenant.
-        // This ensures that each tenant can have at least one layer resident at a given time,
-        // ensuring forward progress for a single Timeline::get in that tenant.
-        // It's a questionable heuristic since there are many Timeline::get
-        // requests going on and multiple layers are needed, and, at least in Neon prod,
-        // the median layer file size is much smaller than the compaction target size.
-        // We could be better here, e.g., sum of all L0 layers + most recent L1 layer.
-        // That's what's typically used by the various background loops.
+        // `min_resident_size` defaults to a working-set estimate: the sum of all
+        // L0 delta layers plus the most recently accessed non-L0 layer per timeline. This is
+        // roughly the data a typical page reconstruction needs to make forward
+        // progress, and it's what the various background loops already use.
+        //
+        // When the tenant has no L0 layers (e.g. freshly compacted), the
+        // working-set estimate can be smaller than a single layer, so we fall
+        // back to the maximum layer file size, preserving the old guarantee that
+        // at least one layer stays resident.
         //
         // The default can be overriden with a fixed value in the tenant conf.
         // A default override can be put in the default tenant conf in the pageserver.toml.
@@ -605,9 +1134,16 @@ async fn collect_eviction_candidates(
                 tenant.tenant_id()
             );
             s
+        } else if have_l0 {
+            info!(
+                "using working-set min resident size {} for tenant {}",
+                working_set_size,
+                tenant.tenant_id()
+            );
+            working_set_size
         } else {
             info!(
-                "using max layer size {} for tenant {}",
+                "no L0 layers, using max layer size {} for tenant {}",
                 max_layer_size,
                 tenant.tenant_id()
             );
@@ -621,10 +1157,40 @@ async fn collect_eviction_candidates(
         let mut cumsum: i128 = 0;
         for (timeline, layer_info) in tenant_candidates.into_iter() {
             let file_size = layer_info.file_size();
+            // GDSF priority `H = L + (freq * cost) / size`. `freq` is the
+            // layer's (lazily halved) access counter and `cost` is the
+            // re-fetch cost: a fixed per-request term (`refetch_fixed_cost_bytes`,
+            // modeling remote-storage request overhead that's paid regardless of
+            // size) plus a size-proportional term (`refetch_cost_per_byte`). A
+            // pure `cost = size * refetch_cost_per_byte` would cancel the `/
+            // size` below and make `H` size-insensitive; the fixed term is what
+            // keeps small, frequently-read layers prioritized for residency over
+            // large ones with equal `freq`. A zero file size (shouldn't happen
+            // for a persistent layer) is treated as maximally evictable. Layers
+            // with `freq == 0` all collapse to `H == L` and are broken apart by
+            // the `last_activity_ts` LRU tiebreaker below.
+            let freq = layer_info.access_count();
+            // This pass considers the layer for eviction; decay its counter by
+            // half so `freq` reflects recent popularity rather than a
+            // lifetime-accumulated total that only ever grows.
+            layer_info.layer.record_disk_usage_eviction_pass();
+            let score = if file_size == 0 {
+                gdsf_clock
+            } else {
+                let cost = refetch_fixed_cost_bytes as f64 + file_size as f64 * refetch_cost_per_byte;
+                gdsf_clock + (freq as f64 * cost) / (file_size as f64)
+            };
+            // Scope to the requested filesystem, if any: a layer on another
+            // device can't relieve this device's pressure.
+            if device_filter.is_some_and(|dev| dev != layer_info.device_id()) {
+                cumsum += i128::from(file_size);
+                continue;
+            }
             let candidate = EvictionCandidate {
                 timeline,
                 last_activity_ts: layer_info.last_activity_ts,
                 layer: layer_info.layer,
+                score,
             };
             if cumsum > min_resident_size as i128 {
                 above_min_resident_size.push(candidate);
@@ -635,12 +1201,30 @@ async fn collect_eviction_candidates(
         }
     }
 
-    // The MinResidentSizePartitionedCandidates struct expects these to be sorted this way
-    above_min_resident_size.sort_unstable_by_key(|c| c.last_activity_ts);
-    below_min_resident_size.sort_unstable_by_key(|c| c.last_activity_ts);
+    // Sort each partition into eviction order: LRU by timestamp, or ascending
+    // GDSF priority (lowest `H` evicted first). `into_iter_in_eviction_order`
+    // debug-asserts the partitions are sorted to match `eviction_order`.
+    match eviction_order {
+        EvictionOrder::AbsoluteOrder => {
+            above_min_resident_size.sort_unstable_by_key(|c| c.last_activity_ts);
+            below_min_resident_size.sort_unstable_by_key(|c| c.last_activity_ts);
+        }
+        EvictionOrder::GreedyDualSizeFrequency => {
+            // Ascending priority, with `last_activity_ts` LRU as the tiebreaker
+            // so zero-frequency layers (equal `H`) degrade to plain LRU.
+            let by_priority_then_lru = |a: &EvictionCandidate, b: &EvictionCandidate| {
+                a.score
+                    .total_cmp(&b.score)
+                    .then(a.last_activity_ts.cmp(&b.last_activity_ts))
+            };
+            above_min_resident_size.sort_unstable_by(by_priority_then_lru);
+            below_min_resident_size.sort_unstable_by(by_priority_then_lru);
+        }
+    }
 
     Ok(EvictionCandidates::Finished(
         MinResidentSizePartitionedCandidates {
+            order: eviction_order,
             above: above_min_resident_size,
             below: below_min_resident_size,
         },
@@ -675,7 +1259,7 @@ mod filesystem_level_usage {
     use anyhow::Context;
     use nix::{
         dir::Dir,
-        sys::statvfs::{self, Statvfs},
+        sys::statvfs::{self, FsFlags, Statvfs},
     };
     use sync_wrapper::SyncWrapper;
 
@@ -690,38 +1274,171 @@ mod filesystem_level_usage {
         total_bytes: u64,
         /// Free filesystem space
         avail_bytes: u64,
+        /// Total inodes on the filesystem (`f_files`).
+        total_inodes: u64,
+        /// Free inodes available to an unprivileged user (`f_favail`).
+        avail_inodes: u64,
+        /// Whether the filesystem is mounted read-only (`ST_RDONLY`). The kernel
+        /// flips a mount to ro after certain I/O errors; when it does, evicting
+        /// layers can never actually free space.
+        read_only: bool,
+        /// The `st_dev` of this filesystem, so candidates can be scoped to the
+        /// device whose pressure they can actually relieve.
+        device_id: u64,
     }
 
-    impl super::Usage for Usage<'_> {
-        fn has_pressure(&self) -> bool {
-            let usage_pct =
-                (100.0 * (1.0 - ((self.avail_bytes as f64) / (self.total_bytes as f64)))) as u64;
+    impl Usage<'_> {
+        /// Whether eviction can even proceed on this filesystem. A read-only
+        /// mount cannot have layer files removed, so eviction would spin
+        /// uselessly.
+        pub fn is_read_only(&self) -> bool {
+            self.read_only
+        }
+    }
+
+    impl Usage<'_> {
+        fn usage_pct(&self) -> u64 {
+            (100.0 * (1.0 - ((self.avail_bytes as f64) / (self.total_bytes as f64)))) as u64
+        }
+
+        fn inode_usage_pct(&self) -> u64 {
+            if self.total_inodes == 0 {
+                return 0;
+            }
+            (100.0 * (1.0 - ((self.avail_inodes as f64) / (self.total_inodes as f64)))) as u64
+        }
 
-            let pressures = [
+        /// Which thresholds, if any, are currently tripped. Ordered so operators
+        /// can tell byte pressure from inode pressure in logs.
+        fn pressures(&self) -> [(&'static str, bool); 4] {
+            [
                 (
                     "min_avail_bytes",
                     self.avail_bytes < self.config.min_avail_bytes,
                 ),
                 (
                     "max_usage_pct",
-                    usage_pct > self.config.max_usage_pct.get() as u64,
+                    self.usage_pct() > self.config.max_usage_pct.get() as u64,
+                ),
+                (
+                    "min_avail_inodes",
+                    self.config
+                        .min_avail_inodes
+                        .is_some_and(|min| self.avail_inodes < min),
+                ),
+                (
+                    "max_inode_usage_pct",
+                    self.config
+                        .max_inode_usage_pct
+                        .is_some_and(|max| self.inode_usage_pct() > max.get() as u64),
                 ),
-            ];
+            ]
+        }
+    }
+
+    impl super::Usage for Usage<'_> {
+        fn has_pressure(&self) -> bool {
+            self.pressures()
+                .into_iter()
+                .any(|(_, has_pressure)| has_pressure)
+        }
 
-            pressures.into_iter().any(|(_, has_pressure)| has_pressure)
+        fn still_over_target(&self) -> bool {
+            // Drain down to the low watermark. The margin (a fraction of the
+            // high watermark) takes precedence over an absolute `target_usage_pct`;
+            // if neither is set we fall back to the high watermark, i.e. the
+            // historical single-threshold behavior. `min_avail_bytes` is an
+            // absolute floor that always applies, and inode pressure has no
+            // separate low watermark, so we drain until it clears entirely.
+            let high = self.config.max_usage_pct.get() as u64;
+            let target_pct = if let Some(margin) = self.config.eviction_target_margin_pct {
+                high * margin.get() as u64 / 100
+            } else {
+                self.config.target_usage_pct.unwrap_or(self.config.max_usage_pct).get() as u64
+            };
+            let byte_over_target =
+                self.avail_bytes < self.config.min_avail_bytes || self.usage_pct() > target_pct;
+            let inode_pressure = self.pressures()[2].1 || self.pressures()[3].1;
+            byte_over_target || inode_pressure
         }
 
         fn add_available_bytes(&mut self, bytes: u64) {
             self.avail_bytes += bytes;
         }
+
+        fn add_freed_inodes(&mut self, n: u64) {
+            self.avail_inodes += n;
+        }
+    }
+
+    impl Usage<'_> {
+        /// The `st_dev` of the filesystem this usage describes. Candidates on a
+        /// different device cannot relieve pressure here.
+        pub fn device_id(&self) -> u64 {
+            self.device_id
+        }
+
+        /// Filesystem capacity in bytes, for telemetry.
+        pub fn total_bytes(&self) -> u64 {
+            self.total_bytes
+        }
+
+        /// Currently-free filesystem space in bytes, for telemetry.
+        pub fn avail_bytes(&self) -> u64 {
+            self.avail_bytes
+        }
+
+        /// Current byte usage as a whole-number percentage, for telemetry.
+        pub fn usage_percent(&self) -> u64 {
+            self.usage_pct()
+        }
+
+        /// The pressure signals and whether each is currently tripped, in a
+        /// stable order, so each can be exported as its own labelled flag.
+        pub fn pressure_reasons(&self) -> [(&'static str, bool); 4] {
+            self.pressures()
+        }
     }
 
     pub fn get<'a>(
         tenants_dir_fd: &mut SyncWrapper<Dir>,
         config: &'a DiskUsageEvictionTaskConfig,
     ) -> anyhow::Result<Usage<'a>> {
-        let stat: Statvfs = statvfs::fstatvfs(tenants_dir_fd.get_mut())
-            .context("statvfs failed, presumably directory got unlinked")?;
+        get_one(tenants_dir_fd.get_mut(), config)
+    }
+
+    /// Compute usage for a set of data directories, one [`Usage`] per distinct
+    /// filesystem keyed by `st_dev`. Directories that share a device are stat'd
+    /// once: a single filesystem has a single free-space figure regardless of
+    /// how many tenant directories live on it.
+    pub fn get_per_filesystem<'a>(
+        dirs: &mut [SyncWrapper<Dir>],
+        config: &'a DiskUsageEvictionTaskConfig,
+    ) -> anyhow::Result<std::collections::HashMap<u64, Usage<'a>>> {
+        let mut out = std::collections::HashMap::new();
+        for dir in dirs.iter_mut() {
+            let fd = dir.get_mut();
+            let dev = device_of(fd)?;
+            if let std::collections::hash_map::Entry::Vacant(e) = out.entry(dev) {
+                e.insert(get_one(fd, config)?);
+            }
+        }
+        Ok(out)
+    }
+
+    fn device_of(dir: &Dir) -> anyhow::Result<u64> {
+        use std::os::unix::io::AsRawFd;
+        let stat = nix::sys::stat::fstat(dir.as_raw_fd()).context("fstat data directory")?;
+        Ok(stat.st_dev as u64)
+    }
+
+    fn get_one<'a>(
+        dir: &mut Dir,
+        config: &'a DiskUsageEvictionTaskConfig,
+    ) -> anyhow::Result<Usage<'a>> {
+        let device_id = device_of(dir)?;
+        let stat: Statvfs =
+            statvfs::fstatvfs(dir).context("statvfs failed, presumably directory got unlinked")?;
 
         // https://unix.stackexchange.com/a/703650
         let blocksize = if stat.fragment_size() > 0 {
@@ -734,10 +1451,20 @@ mod filesystem_level_usage {
         let avail_bytes = stat.blocks_available() * blocksize;
         let total_bytes = stat.blocks() * blocksize;
 
+        // likewise use files_available (f_favail) for the unprivileged inode count
+        let total_inodes = stat.files();
+        let avail_inodes = stat.files_available();
+
+        let read_only = stat.flags().contains(FsFlags::ST_RDONLY);
+
         Ok(Usage {
             config,
             total_bytes,
             avail_bytes,
+            total_inodes,
+            avail_inodes,
+            read_only,
+            device_id,
         })
     }
 }
@@ -5,11 +5,12 @@ use bytes::Bytes;
 use camino::{Utf8Path, Utf8PathBuf};
 
 use itertools::Itertools;
-use pageserver_api::{key::rel_block_to_key, reltag::RelTag};
+use pageserver_api::{key::{rel_block_to_key, slru_block_to_key, relmap_file_key, twophase_file_key, CHECKPOINT_KEY, CONTROLFILE_KEY}, reltag::{RelTag, SlruKind}};
 use postgres_ffi::{pg_constants, relfile_utils::parse_relfilename, ControlFileData, DBState_DB_SHUTDOWNED, Oid, BLCKSZ};
 use tokio::io::AsyncRead;
 use tracing::{debug, trace, warn};
-use utils::{id::{NodeId, TenantId, TimelineId}, shard::{ShardCount, ShardNumber, TenantShardId}};
+use utils::{id::{NodeId, TenantId, TimelineId}, shard::{ShardCount, ShardNumber, ShardStripeSize, TenantShardId}};
+use pageserver_api::shard::ShardIdentity;
 use walkdir::WalkDir;
 
 use crate::{context::{DownloadBehavior, RequestContext}, import_datadir, task_mgr::TaskKind, tenant::storage_layer::ImageLayerWriter};
@@ -22,16 +23,26 @@ pub struct PgImportEnv {
     ctx: RequestContext,
     conf: &'static PageServerConf,
     tli: TimelineId,
-    tsi: TenantShardId,
+    tenant_id: TenantId,
+    /// One identity per shard the datadir is imported into. A single
+    /// unsharded entry reproduces the previous behaviour.
+    shards: Vec<ShardIdentity>,
+}
+
+/// An image layer writer paired with the shard it belongs to, so `put_image`
+/// can route each key to the shards that own it.
+struct ShardWriter {
+    shard: ShardIdentity,
+    writer: ImageLayerWriter,
 }
 
 impl PgImportEnv {
 
-    pub async fn init() -> anyhow::Result<PgImportEnv> {
+    pub async fn init(shard_count: ShardCount, stripe_size: ShardStripeSize) -> anyhow::Result<PgImportEnv> {
         let ctx: RequestContext = RequestContext::new(TaskKind::DebugTool, DownloadBehavior::Error);
         let config = toml_edit::Document::new();
         let conf = PageServerConf::parse_and_validate(
-            NodeId(42), 
+            NodeId(42),
             &config,
             &Utf8PathBuf::from("layers")
         )?;
@@ -39,37 +50,55 @@ impl PgImportEnv {
 
         let tni = TenantId::from_str("42424242424242424242424242424242")?;
         let tli = TimelineId::from_str("42424242424242424242424242424242")?;
-        let tsi = TenantShardId {
-            tenant_id: tni,
-            shard_number: ShardNumber(0),
-            shard_count: ShardCount(0),
-        };
+        let shards = Self::build_shards(shard_count, stripe_size)?;
 
         Ok(PgImportEnv {
             ctx,
-            conf, 
+            conf,
             tli,
-            tsi,
+            tenant_id: tni,
+            shards,
         })
     }
 
+    fn build_shards(count: ShardCount, stripe_size: ShardStripeSize) -> anyhow::Result<Vec<ShardIdentity>> {
+        if count.count() <= 1 {
+            Ok(vec![ShardIdentity::unsharded()])
+        } else {
+            (0..count.count())
+                .map(|n| ShardIdentity::new(ShardNumber(n), count, stripe_size).map_err(anyhow::Error::from))
+                .collect()
+        }
+    }
+
     pub async fn import_datadir(&mut self, pgdata_path: &Utf8Path, _tenant_path: &Utf8Path) -> anyhow::Result<()> {
 
         let pgdata_lsn = import_datadir::get_lsn_from_controlfile(&pgdata_path)?.align();
 
         let range = Key::MIN..Key::NON_L0_MAX;
-        let mut one_big_layer = ImageLayerWriter::new(
-            &self.conf,
-            self.tli,
-            self.tsi,
-            &range,
-            pgdata_lsn,
-            &self.ctx,
-        ).await?;
+        // One image layer per shard; each key is routed to the shards that own
+        // it via `ShardIdentity::is_key_local`.
+        let mut writers = Vec::with_capacity(self.shards.len());
+        for shard in &self.shards {
+            let tsi = TenantShardId {
+                tenant_id: self.tenant_id,
+                shard_number: shard.number,
+                shard_count: shard.count,
+            };
+            let writer = ImageLayerWriter::new(
+                self.conf,
+                self.tli,
+                tsi,
+                &range,
+                pgdata_lsn,
+                &self.ctx,
+            ).await?;
+            writers.push(ShardWriter { shard: *shard, writer });
+        }
 
         // Import ordinary databases, DEFAULTTABLESPACE_OID is smaller than GLOBALTABLESPACE_OID, so import them first
         // Traverse database in increasing oid order
-        WalkDir::new(pgdata_path.join("base"))
+        let dboids = WalkDir::new(pgdata_path.join("base"))
             .max_depth(1)
             .into_iter()
             .filter_map(|entry| {
@@ -77,101 +106,83 @@ impl PgImportEnv {
                     path.file_name().to_string_lossy().parse::<i32>().ok()
                 })
             })
-            .sorted()
-            .for_each(|dboid| {
-                let path = pgdata_path.join("base").join(dboid.to_string());
-                self.import_db(&mut one_big_layer, &path, pg_constants::DEFAULTTABLESPACE_OID).await;
-            });
+            .sorted();
+        for dboid in dboids {
+            let path = pgdata_path.join("base").join(dboid.to_string());
+            self.import_dir(&mut writers, pgdata_path, &path).await?;
+        }
 
         // global catalogs now
-        self.import_db(&mut one_big_layer, &pgdata_path.join("global"), postgres_ffi::pg_constants::GLOBALTABLESPACE_OID).await?;
+        self.import_dir(&mut writers, pgdata_path, &pgdata_path.join("global")).await?;
+
+        // SLRU segments, prepared-transaction state files and friends: these
+        // don't live under base/global, so import_file's own path-prefix
+        // matching (pg_xact/, pg_multixact/, pg_twophase/) is what picks the
+        // right key for each, same as for relation files above.
+        for dir in [
+            "pg_xact",
+            "pg_multixact/offsets",
+            "pg_multixact/members",
+            "pg_twophase",
+        ] {
+            self.import_dir(&mut writers, pgdata_path, &pgdata_path.join(dir)).await?;
+        }
 
-        
-        one_big_layer.finish_layer(&self.ctx).await?;
+        for shard_writer in writers {
+            shard_writer.writer.finish_layer(&self.ctx).await?;
+        }
 
         // should we anything about the wal?
 
         Ok(())
     }
 
-    async fn import_db(
+    /// Walk every entry directly under `dir` (non-recursively) and hand it to
+    /// [`Self::import_file`], which is the single entry point that knows how
+    /// to route each kind of datadir file (relation, SLRU segment, twophase
+    /// state, control file, relmap) by its path relative to `pgdata_path`.
+    async fn import_dir(
         &mut self,
-        layer_writer: &mut ImageLayerWriter,
-        path: &Utf8PathBuf,
-        spcnode: u32
+        writers: &mut [ShardWriter],
+        pgdata_path: &Utf8Path,
+        dir: &Utf8Path,
     ) -> anyhow::Result<()> {
-
-        WalkDir::new(path)
+        let entries = WalkDir::new(dir)
             .max_depth(1)
             .into_iter()
             .filter_map(|entry| {
-                entry.ok().and_then(|path| {
-                    let relfile = path.file_name().to_string_lossy();
-                    parse_relfilename(&relfile).ok()
-                })
+                entry
+                    .ok()
+                    .and_then(|e| Utf8PathBuf::from_path_buf(e.into_path()).ok())
             })
-            .sorted()
-            .for_each(|a|{
-                self.import_rel_file();
-            });
+            .filter(|p| p != dir)
+            .sorted();
+
+        for entry in entries {
+            let rel_path = entry.strip_prefix(pgdata_path).unwrap_or(&entry);
+            let len = std::fs::metadata(&entry)?.len() as usize;
+            let mut reader = tokio::fs::File::open(&entry).await?;
+            self.import_file(writers, rel_path.as_std_path(), &mut reader, len)
+                .await?;
+        }
 
         Ok(())
     }
 
-    async fn import_rel_file(
-        &mut self,
-        layer_writer: &mut ImageLayerWriter,
-        path: &Utf8PathBuf,
-        spcnode: u32
-    ) -> anyhow::Result<()> {
-
-        let mut reader = tokio::fs::File::open(path).await?;
-        let len = std::fs::metadata(path)?.len();
-
-        let mut buf: [u8; 8192] = [0u8; 8192];
-
-        ensure!(len % BLCKSZ as usize == 0);
-        let nblocks = len / BLCKSZ as usize;
-
-        let rel = RelTag {
-            spcnode: spcoid,
-            dbnode: dboid,
-            relnode,
-            forknum,
-        };
-
-        let mut blknum: u32 = segno * (1024 * 1024 * 1024 / BLCKSZ as u32);
-
-        loop {
-            let r = reader.read_exact(&mut buf).await;
-            match r {
-                Ok(_) => {
-                    let key = rel_block_to_key(rel, blknum);
-                    layer_writer.put_image(key, Bytes::copy_from_slice(&buf), &self.ctx).await?;
-                }
-
-                Err(err) => match err.kind() {
-                    std::io::ErrorKind::UnexpectedEof => {
-                        // reached EOF. That's expected.
-                        let relative_blknum = blknum - segno * (1024 * 1024 * 1024 / BLCKSZ as u32);
-                        ensure!(relative_blknum == nblocks as u32, "unexpected EOF");
-                        break;
-                    }
-                    _ => {
-                        bail!("error reading file {}: {:#}", path.as_display(), err);
-                    }
-                },
-            };
-            blknum += 1;
+    /// Writes `img` at `key` into every shard that owns it.
+    async fn put_image(&self, writers: &mut [ShardWriter], key: Key, img: Bytes) -> anyhow::Result<()> {
+        for shard_writer in writers.iter_mut() {
+            if shard_writer.shard.is_key_local(&key) {
+                shard_writer.writer.put_image(key, img.clone(), &self.ctx).await?;
+            }
         }
-
         Ok(())
     }
 
     async fn import_file(
         // modification: &mut DatadirModification<'_>,
         &mut self,
-        layer_writer: &mut ImageLayerWriter,
+        writers: &mut [ShardWriter],
         file_path: &Path,
         reader: &mut (impl AsyncRead + Send + Sync + Unpin),
         len: usize,
@@ -192,31 +203,30 @@ impl PgImportEnv {
             let dbnode = 0;
     
             match file_name.as_ref() {
-                // "pg_control" => {
-                //     let bytes = read_all_bytes(reader).await?;
-    
-                //     // Extract the checkpoint record and import it separately.
-                //     let pg_control = ControlFileData::decode(&bytes[..])?;
-                //     let checkpoint_bytes = pg_control.checkPointCopy.encode()?;
-                //     // modification.put_checkpoint(checkpoint_bytes)?;
-                //     debug!("imported control file");
-    
-                //     // Import it as ControlFile
-                //     // modification.put_control_file(bytes)?;
-                //     return Ok(Some(pg_control));
-                // }
-                // "pg_filenode.map" => {
-                //     // let bytes = read_all_bytes(reader).await?;
-                //     // modification
-                //     //     .put_relmap_file(spcnode, dbnode, bytes, ctx)
-                //     //     .await?;
-                //     debug!("imported relmap file")
-                // }
+                "pg_control" => {
+                    let bytes = read_all_bytes(reader).await?;
+
+                    // Extract the checkpoint record and import it separately.
+                    let pg_control = ControlFileData::decode(&bytes[..])?;
+                    let checkpoint_bytes = pg_control.checkPointCopy.encode()?;
+                    self.put_image(writers, CHECKPOINT_KEY, checkpoint_bytes).await?;
+                    debug!("imported control file");
+
+                    // Import the raw control file under its own key as well.
+                    self.put_image(writers, CONTROLFILE_KEY, bytes).await?;
+                    return Ok(Some(pg_control));
+                }
+                "pg_filenode.map" => {
+                    let bytes = read_all_bytes(reader).await?;
+                    let key = relmap_file_key(spcnode, dbnode);
+                    self.put_image(writers, key, bytes).await?;
+                    debug!("imported relmap file")
+                }
                 "PG_VERSION" => {
                     debug!("ignored PG_VERSION file");
                 }
                 _ => {
-                    self.import_rel(layer_writer, file_path, spcnode, dbnode, reader, len).await?;
+                    self.import_rel(writers, file_path, spcnode, dbnode, reader, len).await?;
                     debug!("imported rel creation");
                 }
             }
@@ -230,44 +240,36 @@ impl PgImportEnv {
                 .parse()?;
     
             match file_name.as_ref() {
-                // "pg_filenode.map" => {
-                //     let bytes = read_all_bytes(reader).await?;
-                //     modification
-                //         .put_relmap_file(spcnode, dbnode, bytes, ctx)
-                //         .await?;
-                //     debug!("imported relmap file")
-                // }
+                "pg_filenode.map" => {
+                    let bytes = read_all_bytes(reader).await?;
+                    let key = relmap_file_key(spcnode, dbnode);
+                    self.put_image(writers, key, bytes).await?;
+                    debug!("imported relmap file")
+                }
                 "PG_VERSION" => {
                     debug!("ignored PG_VERSION file");
                 }
                 _ => {
-                    self.import_rel(layer_writer, file_path, spcnode, dbnode, reader, len).await?;
+                    self.import_rel(writers, file_path, spcnode, dbnode, reader, len).await?;
                     debug!("imported rel creation");
                 }
             }
-        // } else if file_path.starts_with("pg_xact") {
-        //     let slru = SlruKind::Clog;
-    
-        //     import_slru(modification, slru, file_path, reader, len, ctx).await?;
-        //     debug!("imported clog slru");
-        // } else if file_path.starts_with("pg_multixact/offsets") {
-        //     let slru = SlruKind::MultiXactOffsets;
-    
-        //     import_slru(modification, slru, file_path, reader, len, ctx).await?;
-        //     debug!("imported multixact offsets slru");
-        // } else if file_path.starts_with("pg_multixact/members") {
-        //     let slru = SlruKind::MultiXactMembers;
-    
-        //     import_slru(modification, slru, file_path, reader, len, ctx).await?;
-        //     debug!("imported multixact members slru");
-        // } else if file_path.starts_with("pg_twophase") {
-        //     let xid = u32::from_str_radix(file_name.as_ref(), 16)?;
-    
-        //     let bytes = read_all_bytes(reader).await?;
-        //     modification
-        //         .put_twophase_file(xid, Bytes::copy_from_slice(&bytes[..]), ctx)
-        //         .await?;
-        //     debug!("imported twophase file");
+        } else if file_path.starts_with("pg_xact") {
+            self.import_slru(writers, SlruKind::Clog, file_path, reader, len).await?;
+            debug!("imported clog slru");
+        } else if file_path.starts_with("pg_multixact/offsets") {
+            self.import_slru(writers, SlruKind::MultiXactOffsets, file_path, reader, len).await?;
+            debug!("imported multixact offsets slru");
+        } else if file_path.starts_with("pg_multixact/members") {
+            self.import_slru(writers, SlruKind::MultiXactMembers, file_path, reader, len).await?;
+            debug!("imported multixact members slru");
+        } else if file_path.starts_with("pg_twophase") {
+            let xid = u32::from_str_radix(file_name.as_ref(), 16)?;
+
+            let bytes = read_all_bytes(reader).await?;
+            let key = twophase_file_key(xid);
+            self.put_image(writers, key, bytes).await?;
+            debug!("imported twophase file");
         } else if file_path.starts_with("pg_wal") {
             debug!("found wal file in base section. ignore it");
         // } else if file_path.starts_with("zenith.signal") {
@@ -310,11 +312,62 @@ impl PgImportEnv {
     }
     
 
+    // subroutine of import_file(), to load one SLRU segment (clog, multixact
+    // offsets/members). SLRU segments are a flat array of 8KB pages, addressed
+    // by the segment number parsed from the (hex) filename.
+    async fn import_slru(
+        &self,
+        writers: &mut [ShardWriter],
+        kind: SlruKind,
+        path: &Path,
+        reader: &mut (impl AsyncRead + Unpin),
+        len: usize,
+    ) -> anyhow::Result<()> {
+        trace!("importing slru file {}", path.display());
+
+        let filename = &path
+            .file_name()
+            .expect("missing slru filename")
+            .to_string_lossy();
+        let segno = u32::from_str_radix(filename, 16)?;
+
+        ensure!(len % BLCKSZ as usize == 0);
+        let nblocks = len / BLCKSZ as usize;
+        ensure!(nblocks <= pg_constants::SLRU_PAGES_PER_SEGMENT as usize);
+
+        let mut buf: [u8; 8192] = [0u8; 8192];
+        let mut blknum: u32 = 0;
+
+        loop {
+            let r = reader.read_exact(&mut buf).await;
+            match r {
+                Ok(_) => {
+                    let key = slru_block_to_key(kind, segno, blknum);
+                    self.put_image(writers, key, Bytes::copy_from_slice(&buf)).await?;
+                }
+
+                Err(err) => match err.kind() {
+                    std::io::ErrorKind::UnexpectedEof => {
+                        // reached EOF. That's expected.
+                        ensure!(blknum == nblocks as u32, "unexpected EOF");
+                        break;
+                    }
+                    _ => {
+                        bail!("error reading file {}: {:#}", path.display(), err);
+                    }
+                },
+            };
+            blknum += 1;
+        }
+
+        Ok(())
+    }
+
     // subroutine of import_timeline_from_postgres_datadir(), to load one relation file.
     async fn import_rel(
         // modification: &mut DatadirModification<'_>,
         &self,
-        layer_writer: &mut ImageLayerWriter,
+        writers: &mut [ShardWriter],
         path: &Path,
         spcoid: Oid,
         dboid: Oid,
@@ -353,10 +406,8 @@ impl PgImportEnv {
             match r {
                 Ok(_) => {
                     let key = rel_block_to_key(rel, blknum);
-                    layer_writer.put_image(key, Bytes::copy_from_slice(&buf), &self.ctx).await?;
-                    // if modification.tline.get_shard_identity().is_key_local(&key) {
-                    //     modification.put_rel_page_image(rel, blknum, Bytes::copy_from_slice(&buf))?;
-                    // }
+                    // Routes the block to whichever shards own this key.
+                    self.put_image(writers, key, Bytes::copy_from_slice(&buf)).await?;
                 }
 
                 Err(err) => match err.kind() {
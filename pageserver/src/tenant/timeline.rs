@@ -0,0 +1,79 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::disk_usage_eviction_task::LayerLevel;
+use crate::tenant::storage_layer::PersistentLayer;
+use crate::tenant::Timeline;
+
+/// Snapshot of a timeline's locally-resident layers, as seen by the
+/// disk-usage eviction task.
+pub struct TimelineDiskUsageEvictionInfo {
+    pub resident_layers: Vec<LocalLayerInfoForDiskUsageEviction>,
+    /// Size of the largest resident layer, if any.
+    pub max_layer_size: Option<u64>,
+}
+
+/// Per-layer facts the disk-usage eviction task needs, decoupled from layer
+/// internals so `disk_usage_eviction_task` doesn't have to reach into
+/// `storage_layer` itself.
+pub struct LocalLayerInfoForDiskUsageEviction {
+    pub layer: Arc<dyn PersistentLayer>,
+    pub last_activity_ts: SystemTime,
+    level: LayerLevel,
+    /// Access counter backing the GDSF `freq` term. Halved on each disk-usage
+    /// eviction pass that considers the layer, so it decays rather than growing
+    /// unbounded.
+    access_count: u64,
+    /// Identifier of the filesystem device the layer's backing file lives on
+    /// (`stat::st_dev`), used to scope eviction to a single pressured device.
+    device_id: u64,
+}
+
+impl LocalLayerInfoForDiskUsageEviction {
+    pub fn file_size(&self) -> u64 {
+        self.layer.file_size()
+    }
+
+    pub fn level(&self) -> LayerLevel {
+        self.level
+    }
+
+    pub fn access_count(&self) -> u64 {
+        self.access_count
+    }
+
+    pub fn device_id(&self) -> u64 {
+        self.device_id
+    }
+}
+
+impl Timeline {
+    /// Collect facts about every locally-resident layer of this timeline, for
+    /// the disk-usage eviction task to rank against all other timelines.
+    pub fn get_local_layers_for_disk_usage_eviction(&self) -> TimelineDiskUsageEvictionInfo {
+        let mut max_layer_size: Option<u64> = None;
+        let resident_layers = self
+            .resident_layer_infos()
+            .into_iter()
+            .map(|info| {
+                max_layer_size = Some(max_layer_size.unwrap_or(0).max(info.layer.file_size()));
+                LocalLayerInfoForDiskUsageEviction {
+                    layer: info.layer,
+                    last_activity_ts: info.last_activity_ts,
+                    level: if info.is_delta0 {
+                        LayerLevel::Delta0
+                    } else {
+                        LayerLevel::Other
+                    },
+                    access_count: info.access_count,
+                    device_id: info.device_id,
+                }
+            })
+            .collect();
+
+        TimelineDiskUsageEvictionInfo {
+            resident_layers,
+            max_layer_size,
+        }
+    }
+}
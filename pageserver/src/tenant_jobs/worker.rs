@@ -1,33 +1,199 @@
-use std::{any::Any, collections::{BinaryHeap, HashMap}, fmt::Debug, hash::Hash, ops::Add, panic::{self, AssertUnwindSafe}, sync::{Condvar, Mutex}, time::{Duration, Instant}};
+use std::{any::Any, collections::{BinaryHeap, HashMap}, fmt::Debug, hash::Hash, ops::Add, panic::{self, AssertUnwindSafe}, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, Ordering}, Arc, Condvar, Mutex}, time::{Duration, Instant}};
 
-pub trait Job: std::fmt::Debug + Send + Clone + PartialOrd + Ord + Hash + 'static {
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+/// How often a blocked worker re-checks its shutdown token, so a cancelled
+/// pool drains within this bound even while parked on the condvar.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Delay before the first retry. Doubles each subsequent attempt, capped at
+/// [`MAX_RETRY_DELAY`].
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Ceiling for the exponential retry backoff.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+pub trait Job: std::fmt::Debug + Send + Clone + PartialOrd + Ord + Hash + Serialize + DeserializeOwned + 'static {
     type ErrorType;
-    fn run(&self) -> Result<Option<Instant>, Self::ErrorType>;
+    fn run(&self, progress: &ProgressHandle, cancel: &CancelToken, children: &ChildSink<Self>) -> Result<Option<Instant>, Self::ErrorType>;
+
+    /// How many times a failing or panicking job is attempted before it is
+    /// parked in the terminal [`JobStatus::Stuck`] dead-letter state. The
+    /// default of 1 means no retry, preserving the previous behaviour.
+    fn max_attempts(&self) -> u32 {
+        1
+    }
+}
+
+/// Backoff before the `attempt`-th retry (0-indexed): `BASE * 2^attempt`,
+/// saturating at [`MAX_RETRY_DELAY`].
+fn retry_delay(attempt: u32) -> Duration {
+    BASE_RETRY_DELAY
+        .checked_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX))
+        .unwrap_or(MAX_RETRY_DELAY)
+        .min(MAX_RETRY_DELAY)
+}
+
+/// Cancellation handle passed into [`Job::run`]. A long-running job should poll
+/// [`CancelToken::is_cancelled`] periodically and, when set, stop promptly and
+/// return `Ok(Some(now))` to yield its worker back. It folds together two
+/// sources: a pool-wide shutdown token and a per-job pause request.
+#[derive(Clone)]
+pub struct CancelToken {
+    pause: Arc<AtomicBool>,
+    shutdown: CancellationToken,
+}
+
+impl CancelToken {
+    /// True once either the pool is shutting down or this job has been paused.
+    pub fn is_cancelled(&self) -> bool {
+        self.pause.load(Ordering::Acquire) || self.shutdown.is_cancelled()
+    }
+}
+
+/// Sink handed to [`Job::run`] so a job can spawn follow-up work. Children
+/// collected here are inserted into the pool under the same lock acquisition in
+/// which the finished parent is updated, so a job and the successors it
+/// schedules appear atomically.
+pub struct ChildSink<J> {
+    inner: Mutex<Vec<J>>,
+}
+
+impl<J> ChildSink<J> {
+    fn new() -> Self {
+        ChildSink {
+            inner: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Queues `job` to be enqueued when the current run completes.
+    pub fn enqueue(&self, job: J) {
+        self.inner.lock().unwrap().push(job);
+    }
+
+    fn take(&self) -> Vec<J> {
+        std::mem::take(&mut self.inner.lock().unwrap())
+    }
+}
+
+/// A progress report a running job publishes through its [`ProgressHandle`].
+#[derive(Debug, Clone)]
+pub struct Progress {
+    pub completed: u64,
+    pub total: u64,
+    pub message: String,
+}
+
+/// Handle passed to [`Job::run`] so a long job can publish incremental
+/// progress. The latest report is visible in [`Pool::snapshot`] while the job
+/// is `Running`.
+#[derive(Clone)]
+pub struct ProgressHandle {
+    inner: Arc<Mutex<Option<Progress>>>,
+}
+
+impl ProgressHandle {
+    fn new() -> Self {
+        ProgressHandle {
+            inner: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Overwrites the latest progress report for the running job.
+    pub fn report(&self, completed: u64, total: u64, message: impl Into<String>) {
+        *self.inner.lock().unwrap() = Some(Progress {
+            completed,
+            total,
+            message: message.into(),
+        });
+    }
+
+    fn latest(&self) -> Option<Progress> {
+        self.inner.lock().unwrap().clone()
+    }
+}
+
+/// A point-in-time, publicly visible view of a job's status, produced by
+/// [`Pool::snapshot`]. Mirrors the private [`JobStatus`] without exposing the
+/// un-`Clone`able error payloads.
+#[derive(Debug, Clone)]
+pub enum JobStatusReport {
+    Ready {
+        scheduled_for: Instant,
+    },
+    Running {
+        worker_name: String,
+        started_at: Instant,
+        progress: Option<Progress>,
+    },
+    Paused {
+        scheduled_for: Instant,
+    },
+    Stuck {
+        message: String,
+    },
 }
 
 #[derive(Debug)]
 enum JobError<J: Job> {
     Panic(Box<dyn Any + Send>),
     Error(J::ErrorType),
+    /// A `Stuck` job recovered from a checkpoint. The original error cannot be
+    /// deserialized, so only its `Debug` rendering survives a restart.
+    Persisted(String),
 }
 
 #[derive(Debug)]
 enum JobStatus<J: Job> where J::ErrorType: Debug {
     Ready {
         scheduled_for: Instant,
+        /// Number of failed attempts so far; 0 on first schedule.
+        attempt: u32,
     },
     Running {
         worker_name: String,
         started_at: Instant,
+        progress: ProgressHandle,
+        /// Set by [`Pool::pause`] to ask the running job to yield.
+        pause: Arc<AtomicBool>,
+        attempt: u32,
+    },
+    /// Removed from `queue` but retained in `status`; [`Pool::resume`] puts it
+    /// back. Keeps the schedule it had when paused.
+    Paused {
+        scheduled_for: Instant,
+        attempt: u32,
     },
     Stuck(JobError<J>),
 }
 
+/// On-disk form of a [`JobStatus`]. `Instant` is not serializable and is
+/// meaningless across a restart, so `Ready` schedules are stored as the
+/// `Duration` still remaining at checkpoint time and translated back to an
+/// `Instant` relative to `now` on load.
+#[derive(Serialize, Deserialize)]
+enum PersistedStatus {
+    Ready { remaining: Duration, attempt: u32 },
+    /// The process died while this job was executing; it is re-run on load.
+    Running { attempt: u32 },
+    Paused { remaining: Duration, attempt: u32 },
+    Stuck { message: String },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "J: Serialize + DeserializeOwned")]
+struct PersistedJob<J> {
+    job: J,
+    status: PersistedStatus,
+}
+
 // TODO make this generic event, put in different module
 #[derive(Debug)]
 struct Deadline<J: Job> where J::ErrorType: Debug {
     start_by: Instant,
     job: J,
+    attempt: u32,
 }
 
 impl<J: Job> PartialOrd for Deadline<J> where J::ErrorType: Debug {
@@ -61,9 +227,15 @@ struct JobStatusTable<J: Job> where J::ErrorType: Debug {
 
 impl<J: Job> JobStatusTable<J> where J::ErrorType: Debug {
     fn pop_due(&mut self) -> Option<Deadline<J>> {
-        if let Some(deadline) = self.queue.peek() {
-            if Instant::now() > deadline.start_by {
-                return self.queue.pop();
+        while let Some(deadline) = self.queue.peek() {
+            if Instant::now() <= deadline.start_by {
+                break;
+            }
+            let deadline = self.queue.pop().expect("peeked");
+            // A job may have been paused (or otherwise left its `Ready` state)
+            // after being queued; its stale queue entry is dropped here.
+            if matches!(self.status.get(&deadline.job), Some(JobStatus::Ready { .. })) {
+                return Some(deadline);
             }
         }
         None
@@ -79,85 +251,357 @@ impl<J: Job> JobStatusTable<J> where J::ErrorType: Debug {
 pub struct Pool<J: Job> where J::ErrorType: Debug {
     job_table: Mutex<JobStatusTable<J>>,
     condvar: Condvar,  // Notified when idle worker should wake up
+
+    /// Where the job table is checkpointed so it survives a restart. Rewritten
+    /// after every status transition while the table lock is held.
+    checkpoint_path: PathBuf,
 }
 
 impl<J: Job> Pool<J> where J::ErrorType: Debug {
-    fn new() -> Self {
+    fn new(checkpoint_path: PathBuf) -> Self {
+        let table = Self::load(&checkpoint_path);
         Pool {
-            job_table: Mutex::new(JobStatusTable::<J> {
-                status: HashMap::<J, JobStatus<J>>::new(),
-                queue: BinaryHeap::<Deadline<J>>::new(),
-            }),
+            job_table: Mutex::new(table),
             condvar: Condvar::new(),
+            checkpoint_path,
         }
     }
 
-    fn worker_main(&self, worker_name: String) -> anyhow::Result<()> {
+    /// Reloads a previously checkpointed job table, translating persisted
+    /// schedules back into `Instant`s relative to now. A missing or unreadable
+    /// checkpoint yields an empty table — we never refuse to start over a
+    /// corrupt file.
+    fn load(path: &Path) -> JobStatusTable<J> {
+        let mut table = JobStatusTable::<J> {
+            status: HashMap::new(),
+            queue: BinaryHeap::new(),
+        };
+
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(_) => return table,
+        };
+        let persisted: Vec<PersistedJob<J>> = match rmp_serde::from_slice(&bytes) {
+            Ok(persisted) => persisted,
+            Err(e) => {
+                println!("Could not decode job checkpoint, starting empty: {e}");
+                return table;
+            }
+        };
+
+        let now = Instant::now();
+        for PersistedJob { job, status } in persisted {
+            match status {
+                PersistedStatus::Ready { remaining, attempt } => {
+                    let scheduled_for = now + remaining;
+                    table.status.insert(job.clone(), JobStatus::Ready { scheduled_for, attempt });
+                    table.queue.push(Deadline { job, start_by: scheduled_for, attempt });
+                }
+                // The process died mid-flight: re-run the job immediately,
+                // preserving the attempt count so retries aren't reset.
+                PersistedStatus::Running { attempt } => {
+                    table.status.insert(job.clone(), JobStatus::Ready { scheduled_for: now, attempt });
+                    table.queue.push(Deadline { job, start_by: now, attempt });
+                }
+                // A paused job stays paused across a restart; it is not queued
+                // until explicitly resumed.
+                PersistedStatus::Paused { remaining, attempt } => {
+                    table.status.insert(job, JobStatus::Paused { scheduled_for: now + remaining, attempt });
+                }
+                // Dead-letter jobs are kept for inspection, not retried.
+                PersistedStatus::Stuck { message } => {
+                    table.status.insert(job, JobStatus::Stuck(JobError::Persisted(message)));
+                }
+            }
+        }
+        table
+    }
+
+    /// Serializes the current job table to disk. Called under the table lock
+    /// after every transition so a crash loses at most the in-flight run.
+    fn checkpoint(&self, job_table: &JobStatusTable<J>) {
+        let now = Instant::now();
+        let persisted: Vec<PersistedJob<J>> = job_table
+            .status
+            .iter()
+            .map(|(job, status)| {
+                let status = match status {
+                    JobStatus::Ready { scheduled_for, attempt } => PersistedStatus::Ready {
+                        remaining: scheduled_for.saturating_duration_since(now),
+                        attempt: *attempt,
+                    },
+                    JobStatus::Running { attempt, .. } => PersistedStatus::Running {
+                        attempt: *attempt,
+                    },
+                    JobStatus::Paused { scheduled_for, attempt } => PersistedStatus::Paused {
+                        remaining: scheduled_for.saturating_duration_since(now),
+                        attempt: *attempt,
+                    },
+                    JobStatus::Stuck(e) => PersistedStatus::Stuck {
+                        message: format!("{e:?}"),
+                    },
+                };
+                PersistedJob { job: job.clone(), status }
+            })
+            .collect();
+
+        let bytes = match rmp_serde::to_vec(&persisted) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                println!("Could not encode job checkpoint: {e}");
+                return;
+            }
+        };
+
+        // Write to a temp file and rename so a crash mid-write can't leave a
+        // truncated checkpoint behind.
+        let tmp_path = self.checkpoint_path.with_extension("tmp");
+        if let Err(e) = std::fs::write(&tmp_path, &bytes)
+            .and_then(|()| std::fs::rename(&tmp_path, &self.checkpoint_path))
+        {
+            println!("Could not write job checkpoint: {e}");
+        }
+    }
+
+    fn worker_main(&self, worker_name: String, cancel: CancellationToken) -> anyhow::Result<()> {
         let mut job_table = self.job_table.lock().unwrap();
         loop {
-            if let Some(Deadline {job, ..}) = job_table.pop_due() {
+            // Drain cleanly on shutdown: finish no new job and let the thread
+            // return instead of being killed mid-lock.
+            if cancel.is_cancelled() {
+                return Ok(());
+            }
+
+            if let Some(Deadline {job, attempt, ..}) = job_table.pop_due() {
+                let progress = ProgressHandle::new();
+                let pause = Arc::new(AtomicBool::new(false));
                 job_table.set_status(&job, JobStatus::Running {
                     worker_name: worker_name.clone(),
                     started_at: Instant::now(),
+                    progress: progress.clone(),
+                    pause: pause.clone(),
+                    attempt,
                 });
+                self.checkpoint(&job_table);
 
                 // Run job without holding lock
                 drop(job_table);
+                let token = CancelToken {
+                    pause: pause.clone(),
+                    shutdown: cancel.clone(),
+                };
+                let children = ChildSink::new();
                 let result = panic::catch_unwind(AssertUnwindSafe(|| {
-                    job.run()
+                    job.run(&progress, &token, &children)
                 }));
                 job_table = self.job_table.lock().unwrap();
 
-                // Update job status
+                // Enqueue any successor jobs the run spawned, atomically with
+                // updating the parent below.
+                let spawned = children.take();
+                let had_children = !spawned.is_empty();
+                for child in spawned {
+                    // A spawned child that happens to equal a job already
+                    // tracked in the table (e.g. one `Running` on another
+                    // worker, `Paused`, or `Stuck`) must not clobber that
+                    // entry back to `Ready` — doing so would let a second
+                    // worker pick it up and run it concurrently with itself,
+                    // or silently un-pause/un-stick a job that was
+                    // deliberately parked. Skip the duplicate instead.
+                    if let Some(existing) = job_table.status.get(&child) {
+                        println!(
+                            "Spawned child collides with an already-tracked job in status {existing:?}; skipping duplicate enqueue"
+                        );
+                        continue;
+                    }
+                    let scheduled_for = Instant::now();
+                    job_table.status.insert(child.clone(), JobStatus::Ready {
+                        scheduled_for,
+                        attempt: 0,
+                    });
+                    job_table.queue.push(Deadline {
+                        job: child,
+                        start_by: scheduled_for,
+                        attempt: 0,
+                    });
+                }
+                if had_children {
+                    self.condvar.notify_all();
+                }
+
+                // A job that yielded because it was asked to pause goes to
+                // `Paused`, keeping its work for a later `resume` rather than
+                // being rescheduled. A shutdown yield falls through to the
+                // normal reschedule so the job re-runs after restart.
+                if pause.load(Ordering::Acquire) {
+                    job_table.set_status(&job, JobStatus::Paused {
+                        scheduled_for: Instant::now(),
+                        attempt,
+                    });
+                    self.checkpoint(&job_table);
+                    continue;
+                }
+
+                // Update job status. A failed attempt (error or panic) is
+                // retried with exponential backoff while attempts remain; only
+                // an exhausted job lands in the terminal `Stuck` dead letter.
                 match result {
                     Ok(Ok(Some(reschedule_for))) => {
+                        // Success: a recurring job starts its retry budget over.
                         job_table.set_status(&job, JobStatus::Ready {
                             scheduled_for: reschedule_for,
+                            attempt: 0,
                         });
                         job_table.queue.push(Deadline {
                             job: job.clone(),
                             start_by: reschedule_for,
+                            attempt: 0,
                         })
                     },
                     Ok(Ok(None)) => {
                         job_table.status.remove(&job);
                     },
                     Ok(Err(e)) => {
-                        job_table.set_status(&job, JobStatus::Stuck(JobError::Error(e)));
+                        self.handle_failure(&mut job_table, &job, attempt, JobError::Error(e));
                         println!("Job errored, thread is ok.");
                     },
                     Err(e) => {
-                        job_table.set_status(&job, JobStatus::Stuck(JobError::Panic(e)));
+                        self.handle_failure(&mut job_table, &job, attempt, JobError::Panic(e));
                         println!("Job panicked, thread is ok.");
                     },
                 }
+                self.checkpoint(&job_table);
             } else {
-                match job_table.queue.peek() {
-                    Some(deadline) => {
-                        let wait_time = deadline.start_by.duration_since(Instant::now());
-                        job_table = self.condvar.wait_timeout(job_table, wait_time).unwrap().0;
-                    }
-                    None => {
-                        job_table = self.condvar.wait(job_table).unwrap();
-                    }
-                }
+                // Cap the parked wait so shutdown is noticed promptly even
+                // while we have nothing due.
+                let wait_time = match job_table.queue.peek() {
+                    Some(deadline) => deadline
+                        .start_by
+                        .saturating_duration_since(Instant::now())
+                        .min(SHUTDOWN_POLL_INTERVAL),
+                    None => SHUTDOWN_POLL_INTERVAL,
+                };
+                job_table = self.condvar.wait_timeout(job_table, wait_time).unwrap().0;
             }
         }
     }
 
+    /// Handles a failed (errored or panicked) run. Retries with exponential
+    /// backoff while the job's `max_attempts` budget has entries left,
+    /// otherwise parks it in the terminal `Stuck` dead-letter state. `attempt`
+    /// is the 0-indexed attempt that just failed. The caller checkpoints.
+    fn handle_failure(
+        &self,
+        job_table: &mut JobStatusTable<J>,
+        job: &J,
+        attempt: u32,
+        error: JobError<J>,
+    ) {
+        let next_attempt = attempt + 1;
+        if next_attempt < job.max_attempts() {
+            let scheduled_for = Instant::now() + retry_delay(attempt);
+            job_table.set_status(job, JobStatus::Ready {
+                scheduled_for,
+                attempt: next_attempt,
+            });
+            job_table.queue.push(Deadline {
+                job: job.clone(),
+                start_by: scheduled_for,
+                attempt: next_attempt,
+            });
+        } else {
+            job_table.set_status(job, JobStatus::Stuck(error));
+        }
+    }
+
+    /// Clones the current status table for introspection. Lists every tracked
+    /// job with a public view of its status, including how far along each
+    /// running job has reported itself.
+    pub fn snapshot(&self) -> Vec<(J, JobStatusReport)> {
+        let job_table = self.job_table.lock().unwrap();
+        job_table
+            .status
+            .iter()
+            .map(|(job, status)| {
+                let report = match status {
+                    JobStatus::Ready { scheduled_for, .. } => JobStatusReport::Ready {
+                        scheduled_for: *scheduled_for,
+                    },
+                    JobStatus::Running {
+                        worker_name,
+                        started_at,
+                        progress,
+                        ..
+                    } => JobStatusReport::Running {
+                        worker_name: worker_name.clone(),
+                        started_at: *started_at,
+                        progress: progress.latest(),
+                    },
+                    JobStatus::Paused { scheduled_for, .. } => JobStatusReport::Paused {
+                        scheduled_for: *scheduled_for,
+                    },
+                    JobStatus::Stuck(e) => JobStatusReport::Stuck {
+                        message: format!("{e:?}"),
+                    },
+                };
+                (job.clone(), report)
+            })
+            .collect()
+    }
+
     pub fn queue_job(&self, job: J) {
         let mut job_table = self.job_table.lock().unwrap();
         let scheduled_for = Instant::now();
         job_table.status.insert(job.clone(), JobStatus::Ready {
             scheduled_for,
+            attempt: 0,
         });
         job_table.queue.push(Deadline {
             job: job.clone(),
             start_by: scheduled_for,
+            attempt: 0,
         });
+        self.checkpoint(&job_table);
 
         self.condvar.notify_all();
     }
+
+    /// Pauses a job. A `Ready` job is moved straight to `Paused` (its stale
+    /// queue entry is reaped lazily by `pop_due`); a `Running` job is asked to
+    /// yield via its cancellation handle and the worker parks it once it
+    /// returns. Jobs in other states are left untouched.
+    pub fn pause(&self, job: &J) {
+        let mut job_table = self.job_table.lock().unwrap();
+        match job_table.status.get(job) {
+            Some(JobStatus::Ready { scheduled_for, attempt }) => {
+                let (scheduled_for, attempt) = (*scheduled_for, *attempt);
+                job_table.set_status(job, JobStatus::Paused { scheduled_for, attempt });
+                self.checkpoint(&job_table);
+            }
+            Some(JobStatus::Running { pause, .. }) => {
+                // The worker transitions it to `Paused` when `run` returns.
+                pause.store(true, Ordering::Release);
+            }
+            _ => {}
+        }
+    }
+
+    /// Resumes a previously paused job, restoring its schedule and re-queuing
+    /// it. A no-op for jobs that are not `Paused`.
+    pub fn resume(&self, job: &J) {
+        let mut job_table = self.job_table.lock().unwrap();
+        if let Some(JobStatus::Paused { scheduled_for, attempt }) = job_table.status.get(job) {
+            let (scheduled_for, attempt) = (*scheduled_for, *attempt);
+            job_table.set_status(job, JobStatus::Ready { scheduled_for, attempt });
+            job_table.queue.push(Deadline {
+                job: job.clone(),
+                start_by: scheduled_for,
+                attempt,
+            });
+            self.checkpoint(&job_table);
+            self.condvar.notify_all();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -165,11 +609,12 @@ mod tests {
     use std::time::Duration;
 
     use once_cell::sync::OnceCell;
+    use serde::{Deserialize, Serialize};
 
     use crate::thread_mgr::{self, ThreadKind};
     use super::*;
 
-    #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+    #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
     struct PrintJob {
         to_print: String
     }
@@ -177,10 +622,15 @@ mod tests {
     impl Job for PrintJob {
         type ErrorType = String;
 
-        fn run(&self) -> Result<Option<Instant>, String> {
+        fn run(&self, progress: &ProgressHandle, cancel: &CancelToken, _children: &ChildSink<Self>) -> Result<Option<Instant>, String> {
             if self.to_print == "pls panic" {
                 panic!("AAA");
             }
+            // Yield promptly if asked to pause or shut down.
+            if cancel.is_cancelled() {
+                return Ok(Some(Instant::now()));
+            }
+            progress.report(1, 1, format!("printed {}", self.to_print));
             println!("{}", self.to_print);
             Ok(Some(Instant::now().add(Duration::from_millis(10))))
         }
@@ -190,8 +640,13 @@ mod tests {
 
     #[tokio::test]
     async fn pool_1() {
-        TEST_POOL.set(Pool::<PrintJob>::new()).unwrap();
+        let checkpoint_path = std::env::temp_dir().join("pageserver_test_job_pool_1.msgpack");
+        let _ = std::fs::remove_file(&checkpoint_path);
+        TEST_POOL.set(Pool::<PrintJob>::new(checkpoint_path)).unwrap();
+
+        let cancel = CancellationToken::new();
 
+        let cancel_1 = cancel.clone();
         thread_mgr::spawn(
             ThreadKind::GarbageCollector,  // change this
             None,
@@ -199,10 +654,11 @@ mod tests {
             "test_worker_1",
             true,
             move || {
-                TEST_POOL.get().unwrap().worker_main("test_worker_1".into())
+                TEST_POOL.get().unwrap().worker_main("test_worker_1".into(), cancel_1)
             },
         ).unwrap();
 
+        let cancel_2 = cancel.clone();
         thread_mgr::spawn(
             ThreadKind::GarbageCollector,  // change this
             None,
@@ -210,7 +666,7 @@ mod tests {
             "test_worker_2",
             true,
             move || {
-                TEST_POOL.get().unwrap().worker_main("test_worker_2".into())
+                TEST_POOL.get().unwrap().worker_main("test_worker_2".into(), cancel_2)
             },
         ).unwrap();
 
@@ -219,5 +675,8 @@ mod tests {
         });
 
         tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Let the workers drain and exit cleanly.
+        cancel.cancel();
     }
 }
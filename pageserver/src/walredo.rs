@@ -35,6 +35,7 @@ use anyhow::Context;
 use bytes::{Bytes, BytesMut};
 use pageserver_api::models::{WalRedoManagerProcessStatus, WalRedoManagerStatus};
 use pageserver_api::shard::TenantShardId;
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
@@ -43,7 +44,6 @@ use tokio_util::sync::CancellationToken;
 use tracing::*;
 use utils::lsn::Lsn;
 use utils::sync::gate::Gate;
-use utils::sync::heavier_once_cell;
 
 pub struct GlobalState {
     conf: &'static PageServerConf,
@@ -53,6 +53,16 @@ pub struct GlobalState {
     /// We do this to avoid the Mutex lock inside the `CancellationToken`.
     shutdown_bool: AtomicBool,
     pub(self) spawn_gate: Gate,
+    /// Throttles new [`process::WalRedoProcess`] launches.
+    ///
+    /// Now that reaping runs asynchronously off the executor thread (see
+    /// [`process::WalRedoProcess`], built on [`tokio::process::Child`]), the old
+    /// blocking `wait()` on drop no longer caps run-away spawning as a side
+    /// effect. This semaphore restores that bound explicitly: a failed-launch
+    /// storm is throttled to `wal_redo_process_launch_concurrency` permits
+    /// (defaulting to the number of CPUs) without stalling
+    /// a worker thread inside a syscall.
+    pub(self) launch_semaphore: tokio::sync::Semaphore,
 }
 
 impl GlobalState {
@@ -65,6 +75,9 @@ impl GlobalState {
             shutdown,
             shutdown_bool: AtomicBool::new(false), // if `shutdown` is cancelled already, the task spawned below will set it promptly
             spawn_gate: Gate::default(),
+            launch_semaphore: tokio::sync::Semaphore::new(
+                conf.wal_redo_process_launch_concurrency.max(1),
+            ),
         });
         tokio::spawn({
             let state = Arc::clone(&state);
@@ -91,28 +104,73 @@ impl GlobalState {
 
 ///
 /// This is the real implementation that uses a Postgres process to
-/// perform WAL replay. Only one thread can use the process at a time,
-/// that is controlled by the Mutex. In the future, we might want to
-/// launch a pool of processes to allow concurrent replay of multiple
-/// records.
+/// perform WAL replay. A [`RedoProcessPool`] of warm processes, keyed per
+/// `pg_version`, lets multiple redo requests run concurrently instead of
+/// serializing on a single child: a request checks out an idle process,
+/// applies its records, and returns the process to the pool when done. The
+/// pool grows lazily up to a cap configured via
+/// [`PageServerConf::wal_redo_process_pool_size`].
 ///
 pub struct PostgresRedoManager {
     global_state: Arc<GlobalState>,
     tenant_shard_id: TenantShardId,
     last_redo_at: std::sync::Mutex<Option<Instant>>,
-    /// The current [`process::WalRedoProcess`] that is used by new redo requests.
-    /// We use [`heavier_once_cell`] for coalescing the spawning, but the redo
-    /// requests don't use the [`heavier_once_cell::Guard`] to keep ahold of the
-    /// their process object; we use [`Arc::clone`] for that.
-    /// This is primarily because earlier implementations that didn't  use [`heavier_once_cell`]
-    /// had that behavior; it's probably unnecessary.
-    /// The only merit of it is that if one walredo process encounters an error,
-    /// it can take it out of rotation (= using [`heavier_once_cell::Guard::take_and_deinit`].
-    /// and retry redo, thereby starting the new process, while other redo tasks might
-    /// still be using the old redo process. But, those other tasks will most likely
-    /// encounter an error as well, and errors are an unexpected condition anyway.
-    /// So, probably we could get rid of the `Arc` in the future.
-    redo_process: heavier_once_cell::OnceCell<Arc<process::WalRedoProcess>>,
+    /// Pool of warm [`process::WalRedoProcess`]es available to redo requests.
+    pool: RedoProcessPool,
+    /// The most recent redo failure, for observability via [`Self::status`].
+    last_failure: std::sync::Mutex<Option<LastFailure>>,
+}
+
+/// A bounded pool of warm wal-redo processes, partitioned by `pg_version`.
+///
+/// The [`Semaphore`](tokio::sync::Semaphore) caps the number of processes
+/// checked out at once across all versions; a permit is held for as long as a
+/// checked-out process is in use and released when it is returned to (or
+/// evicted from) the pool. Idle processes are parked in `idle` and handed back
+/// out on the next checkout for the same version. Idle processes hold no
+/// permit, so the live processes of one `pg_version` stay within the cap, but
+/// processes parked for different versions are not counted against each other.
+struct RedoProcessPool {
+    cap: usize,
+    permits: Arc<tokio::sync::Semaphore>,
+    idle: std::sync::Mutex<HashMap<u32, Vec<Arc<process::WalRedoProcess>>>>,
+}
+
+impl RedoProcessPool {
+    fn new(cap: usize) -> Self {
+        RedoProcessPool {
+            cap,
+            permits: Arc::new(tokio::sync::Semaphore::new(cap)),
+            idle: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take an idle process for `pg_version` out of the pool, if any.
+    fn take_idle(&self, pg_version: u32) -> Option<Arc<process::WalRedoProcess>> {
+        let mut idle = self.idle.lock().unwrap();
+        idle.get_mut(&pg_version).and_then(Vec::pop)
+    }
+
+    /// Return a healthy process to the pool so the next checkout can reuse it.
+    fn put_idle(&self, pg_version: u32, proc: Arc<process::WalRedoProcess>) {
+        let mut idle = self.idle.lock().unwrap();
+        idle.entry(pg_version).or_default().push(proc);
+    }
+
+    /// Current occupancy: number of idle processes parked, summed over all
+    /// `pg_version`s, plus the number of in-use permits.
+    fn occupancy(&self) -> (usize, usize) {
+        let idle: usize = self.idle.lock().unwrap().values().map(Vec::len).sum();
+        let in_use = self.cap - self.permits.available_permits();
+        (idle, in_use)
+    }
+
+    /// Drop every idle process, sending SIGKILL as each `Arc` reaches refcount
+    /// zero. In-flight checkouts are unaffected; a checkout that succeeds is
+    /// returned to the pool as usual when it finishes.
+    fn reap_all(&self) {
+        self.idle.lock().unwrap().clear();
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -123,6 +181,94 @@ pub enum Error {
     Other(#[from] anyhow::Error),
 }
 
+/// Classification of a wal-redo failure, used to decide whether a retry is
+/// worthwhile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FailureClass {
+    /// Process crash / IPC reset / timeout — respawning and retrying may
+    /// succeed, so this consumes a retry slot.
+    Transient,
+    /// Malformed record, key mismatch, etc. — deterministic, so retrying the
+    /// same input is pointless and we fail fast.
+    Deterministic,
+}
+
+impl FailureClass {
+    /// Best-effort classification of an error coming out of
+    /// [`process::WalRedoProcess::apply_wal_records`].
+    ///
+    /// IPC/transport failures (a crashed or reset child, a timeout) are
+    /// transient; everything else (a rejected or malformed record) is treated
+    /// as deterministic and is not retried.
+    fn classify(err: &anyhow::Error) -> FailureClass {
+        for cause in err.chain() {
+            if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+                return match io.kind() {
+                    std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::UnexpectedEof
+                    | std::io::ErrorKind::TimedOut => FailureClass::Transient,
+                    _ => FailureClass::Deterministic,
+                };
+            }
+            // `apply_wal_records`'s `tokio::time::timeout(...).context(...)?`
+            // surfaces a timeout as `tokio::time::error::Elapsed`, not an
+            // `io::Error`, so it needs its own chain match arm.
+            if cause.downcast_ref::<tokio::time::error::Elapsed>().is_some() {
+                return FailureClass::Transient;
+            }
+        }
+        FailureClass::Deterministic
+    }
+}
+
+/// The most recent wal-redo failure, surfaced through [`PostgresRedoManager::status`]
+/// so operators can see when redo is flapping.
+#[derive(Debug, Clone, Copy)]
+struct LastFailure {
+    attempts: u32,
+    class: FailureClass,
+}
+
+/// Retry policy applied to transient wal-redo failures. Configured on
+/// [`PageServerConf`] as `wal_redo_retry`.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct WalRedoRetryConfig {
+    /// Maximum number of retry attempts for a transient failure before giving up.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    #[serde(with = "humantime_serde")]
+    pub base_delay: Duration,
+    /// Multiplier applied to the delay after each attempt (exponential backoff).
+    pub backoff_multiplier: f64,
+    /// Upper bound on the per-retry delay.
+    #[serde(with = "humantime_serde")]
+    pub max_delay: Duration,
+}
+
+impl Default for WalRedoRetryConfig {
+    fn default() -> Self {
+        // Matches the historical behavior of a single immediate retry, but with
+        // a small backoff so a crash loop doesn't hammer the launch path.
+        WalRedoRetryConfig {
+            max_attempts: 1,
+            base_delay: Duration::from_millis(100),
+            backoff_multiplier: 2.0,
+            max_delay: Duration::from_secs(3),
+        }
+    }
+}
+
+impl WalRedoRetryConfig {
+    /// Exponential backoff for the `n`-th retry attempt (1-indexed), capped at
+    /// [`max_delay`](Self::max_delay).
+    fn backoff_for(&self, attempt: u32) -> Duration {
+        let factor = self.backoff_multiplier.powi(attempt.saturating_sub(1) as i32);
+        let delay = self.base_delay.as_secs_f64() * factor;
+        Duration::from_secs_f64(delay.min(self.max_delay.as_secs_f64()))
+    }
+}
+
 macro_rules! bail {
     ($($arg:tt)*) => {
         return Err($crate::walredo::Error::Other(::anyhow::anyhow!($($arg)*)));
@@ -210,10 +356,30 @@ impl PostgresRedoManager {
                     chrono::Utc::now().checked_sub_signed(chrono::Duration::from_std(age).ok()?)
                 })
             },
-            process: self
-                .redo_process
-                .get()
-                .map(|p| WalRedoManagerProcessStatus { pid: p.id() }),
+            process: {
+                // Report a representative live process (the first idle one) for
+                // backwards compatibility; the pool occupancy below reflects how
+                // many processes are parked vs. in use.
+                let idle = self.pool.idle.lock().unwrap();
+                idle.values()
+                    .flatten()
+                    .next()
+                    .map(|p| WalRedoManagerProcessStatus { pid: p.id() })
+            },
+            pool: {
+                let (idle, in_use) = self.pool.occupancy();
+                pageserver_api::models::WalRedoManagerPoolStatus {
+                    size: self.pool.cap,
+                    idle,
+                    in_use,
+                }
+            },
+            last_failure: self.last_failure.lock().unwrap().map(|f| {
+                pageserver_api::models::WalRedoManagerLastFailure {
+                    attempts: f.attempts,
+                    transient: f.class == FailureClass::Transient,
+                }
+            }),
         }
     }
 }
@@ -226,12 +392,15 @@ impl PostgresRedoManager {
         global_state: Arc<GlobalState>,
         tenant_shard_id: TenantShardId,
     ) -> PostgresRedoManager {
-        // The actual process is launched lazily, on first request.
+        // The actual processes are launched lazily, on first request, up to
+        // `wal_redo_process_pool_size`.
+        let cap = global_state.conf.wal_redo_process_pool_size.max(1);
         PostgresRedoManager {
             global_state,
             tenant_shard_id,
             last_redo_at: std::sync::Mutex::default(),
-            redo_process: heavier_once_cell::OnceCell::default(),
+            pool: RedoProcessPool::new(cap),
+            last_failure: std::sync::Mutex::default(),
         }
     }
 
@@ -243,7 +412,9 @@ impl PostgresRedoManager {
             if let Some(last_redo_at) = *g {
                 if last_redo_at.elapsed() >= idle_timeout {
                     drop(g);
-                    drop(self.redo_process.get().map(|guard| guard.take_and_deinit()));
+                    // Reap the whole pool down on idle; in-flight checkouts are
+                    // untouched and are returned to the pool when they finish.
+                    self.pool.reap_all();
                 }
             }
         }
@@ -269,39 +440,57 @@ impl PostgresRedoManager {
         *(self.last_redo_at.lock().unwrap()) = Some(Instant::now());
 
         let (rel, blknum) = key.to_rel_block().context("invalid record")?;
-        const MAX_RETRY_ATTEMPTS: u32 = 1;
+        let retry = &self.global_state.conf.wal_redo_retry;
         let mut n_attempts = 0u32;
         loop {
-            let proc: Arc<process::WalRedoProcess> =
-                match self.redo_process.get_or_init_detached().await {
-                    Ok(guard) => Arc::clone(&guard),
-                    Err(permit) => {
-                        // don't hold poison_guard, the launch code can bail
-                        let start = Instant::now();
-                        let proc = Arc::new(
-                            process::WalRedoProcess::launch(
-                                &self.global_state,
-                                self.tenant_shard_id,
-                                pg_version,
-                            )
-                            .map_err(|e| match e {
-                                process::LaunchError::Cancelled => Error::Cancelled,
-                                process::LaunchError::Other(e) => {
-                                    Error::Other(e.context("launch walredo process"))
-                                }
-                            })?,
-                        );
-                        let duration = start.elapsed();
-                        WAL_REDO_PROCESS_LAUNCH_DURATION_HISTOGRAM.observe(duration.as_secs_f64());
-                        info!(
-                            duration_ms = duration.as_millis(),
-                            pid = proc.id(),
-                            "launched walredo process"
-                        );
-                        self.redo_process.set(Arc::clone(&proc), permit);
-                        proc
-                    }
-                };
+            // Hold a pool permit for the whole time the process is checked out;
+            // this bounds the number of concurrent processes to the pool cap.
+            let permit = self
+                .pool
+                .permits
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("semaphore is never closed");
+
+            // Reuse a warm process if one is idle, otherwise launch a new one
+            // (the permit we hold guarantees we stay under the cap).
+            let proc: Arc<process::WalRedoProcess> = match self.pool.take_idle(pg_version) {
+                Some(proc) => proc,
+                None => {
+                    // Throttle launches so a failed-launch storm can't spawn
+                    // processes faster than the runtime can reap them.
+                    let _launch_permit = self
+                        .global_state
+                        .launch_semaphore
+                        .acquire()
+                        .await
+                        .expect("semaphore is never closed");
+                    // don't hold the permit across a bail: the launch code can fail
+                    let start = Instant::now();
+                    let proc = Arc::new(
+                        process::WalRedoProcess::launch(
+                            &self.global_state,
+                            self.tenant_shard_id,
+                            pg_version,
+                        )
+                        .map_err(|e| match e {
+                            process::LaunchError::Cancelled => Error::Cancelled,
+                            process::LaunchError::Other(e) => {
+                                Error::Other(e.context("launch walredo process"))
+                            }
+                        })?,
+                    );
+                    let duration = start.elapsed();
+                    WAL_REDO_PROCESS_LAUNCH_DURATION_HISTOGRAM.observe(duration.as_secs_f64());
+                    info!(
+                        duration_ms = duration.as_millis(),
+                        pid = proc.id(),
+                        "launched walredo process"
+                    );
+                    proc
+                }
+            };
 
             let started_at = std::time::Instant::now();
 
@@ -340,8 +529,9 @@ impl PostgresRedoManager {
                 lsn
             );
 
-            // If something went wrong, don't try to reuse the process. Kill it, and
-            // next request will launch a new one.
+            // If something went wrong, don't return the process to the pool. Let
+            // it die (SIGKILL when the last `Arc` is dropped), and a later
+            // checkout will launch a fresh one.
             if let Err(e) = result.as_ref() {
                 error!(
                     "error applying {} WAL records {}..{} ({} bytes) to key {key}, from base image with LSN {} to reconstruct page image at LSN {} n_attempts={}: {:?}",
@@ -354,41 +544,52 @@ impl PostgresRedoManager {
                     n_attempts,
                     e,
                 );
-                // Avoid concurrent callers hitting the same issue by taking `proc` out of the rotation.
-                // Note that there may be other tasks concurrent with us that also hold `proc`.
-                // We have to deal with that here.
-                // Also read the doc comment on field `self.redo_process`.
+                // The process is not returned to the pool; dropping the `Arc`
+                // below sends SIGKILL once its refcount reaches zero. The pool
+                // permit we hold is released at the end of this iteration,
+                // bounding run-away spawning to the pool cap.
                 //
-                // NB: there may still be other concurrent threads using `proc`.
-                // The last one will send SIGKILL when the underlying Arc reaches refcount 0.
-                //
-                // NB: the drop impl blocks the dropping thread with a wait() system call for
-                // the child process. In some ways the blocking is actually good: if we
-                // deferred the waiting into the background / to tokio if we used `tokio::process`,
-                // it could happen that if walredo always fails immediately, we spawn processes faster
-                // than we can SIGKILL & `wait` for them to exit. By doing it the way we do here,
-                // we limit this risk of run-away to at most $num_runtimes * $num_executor_threads.
-                // This probably needs revisiting at some later point.
-                match self.redo_process.get() {
-                    None => (),
-                    Some(guard) => {
-                        if Arc::ptr_eq(&proc, &*guard) {
-                            // We're the first to observe an error from `proc`, it's our job to take it out of rotation.
-                            guard.take_and_deinit();
-                        } else {
-                            // Another task already spawned another redo process (further up in this method)
-                            // and put it into `redo_process`. Do nothing, our view of the world is behind.
-                        }
-                    }
-                }
-                // The last task that does this `drop()` of `proc` will do a blocking `wait()` syscall.
+                // NB: the drop impl only sends the kill signal; the child is
+                // reaped asynchronously on the runtime, not on this thread.
                 drop(proc);
-            } else if n_attempts != 0 {
-                info!(n_attempts, "retried walredo succeeded");
+            } else {
+                if n_attempts != 0 {
+                    info!(n_attempts, "retried walredo succeeded");
+                }
+                // Healthy process: hand it back so the next request can reuse it.
+                self.pool.put_idle(pg_version, proc);
             }
+            drop(permit);
+
+            let err = match result {
+                Ok(img) => {
+                    // Success: clear any previously recorded failure.
+                    *self.last_failure.lock().unwrap() = None;
+                    return Ok(img);
+                }
+                Err(err) => err,
+            };
+
+            // Classify the failure and record it for `status()`.
+            let class = FailureClass::classify(&err);
             n_attempts += 1;
-            if n_attempts > MAX_RETRY_ATTEMPTS || result.is_ok() {
-                return result.map_err(Error::Other);
+            *self.last_failure.lock().unwrap() = Some(LastFailure {
+                attempts: n_attempts,
+                class,
+            });
+
+            // Deterministic failures won't succeed on retry; fail fast. Transient
+            // failures consume a retry slot and back off before respawning.
+            if class == FailureClass::Deterministic || n_attempts > retry.max_attempts {
+                return Err(Error::Other(err));
+            }
+
+            let backoff = retry.backoff_for(n_attempts);
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = self.global_state.shutdown.cancelled() => {
+                    return Err(Error::Cancelled);
+                }
             }
         }
     }
@@ -449,15 +650,84 @@ impl PostgresRedoManager {
 
 #[cfg(test)]
 mod tests {
-    use super::PostgresRedoManager;
+    use super::{FailureClass, PostgresRedoManager, WalRedoRetryConfig};
     use crate::repository::Key;
     use crate::{config::PageServerConf, walrecord::NeonWalRecord};
     use bytes::Bytes;
     use pageserver_api::shard::TenantShardId;
     use std::str::FromStr;
+    use std::time::Duration;
     use tracing::Instrument;
     use utils::{id::TenantId, lsn::Lsn};
 
+    #[test]
+    fn failure_class_classifies_io_errors_as_transient() {
+        for kind in [
+            std::io::ErrorKind::BrokenPipe,
+            std::io::ErrorKind::ConnectionReset,
+            std::io::ErrorKind::UnexpectedEof,
+            std::io::ErrorKind::TimedOut,
+        ] {
+            let err = anyhow::Error::new(std::io::Error::new(kind, "wal-redo process"));
+            assert_eq!(FailureClass::classify(&err), FailureClass::Transient);
+        }
+    }
+
+    #[test]
+    fn failure_class_classifies_other_errors_as_deterministic() {
+        // An unrelated `io::Error` kind: not one of the IPC-reset signals above.
+        let err = anyhow::Error::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "malformed record",
+        ));
+        assert_eq!(FailureClass::classify(&err), FailureClass::Deterministic);
+
+        // No `io::Error` in the chain at all.
+        let err = anyhow::anyhow!("key mismatch");
+        assert_eq!(FailureClass::classify(&err), FailureClass::Deterministic);
+    }
+
+    #[test]
+    fn failure_class_classifies_wrapped_io_error_via_chain() {
+        // The `io::Error` is a couple of `.context()` layers deep; `classify`
+        // walks the whole chain rather than only checking the top frame.
+        let err = anyhow::Error::new(std::io::Error::new(
+            std::io::ErrorKind::BrokenPipe,
+            "pipe closed",
+        ))
+        .context("apply_wal_records")
+        .context("apply_batch_postgres");
+        assert_eq!(FailureClass::classify(&err), FailureClass::Transient);
+    }
+
+    #[tokio::test]
+    async fn failure_class_classifies_timeout_as_transient() {
+        // Mirrors `apply_wal_records`'s
+        // `tokio::time::timeout(...).context("wal-redo process timed out")?`,
+        // which surfaces as a `tokio::time::error::Elapsed`, not an `io::Error`.
+        let elapsed = tokio::time::timeout(Duration::from_millis(1), std::future::pending::<()>())
+            .await
+            .unwrap_err();
+        let err = anyhow::Error::new(elapsed).context("wal-redo process timed out");
+        assert_eq!(FailureClass::classify(&err), FailureClass::Transient);
+    }
+
+    #[test]
+    fn backoff_for_grows_exponentially_and_caps_at_max_delay() {
+        let retry = WalRedoRetryConfig {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            backoff_multiplier: 2.0,
+            max_delay: Duration::from_secs(1),
+        };
+
+        assert_eq!(retry.backoff_for(1), Duration::from_millis(100));
+        assert_eq!(retry.backoff_for(2), Duration::from_millis(200));
+        assert_eq!(retry.backoff_for(3), Duration::from_millis(400));
+        // Uncapped this would be 800ms * 2 = 1600ms; the max_delay of 1s wins.
+        assert_eq!(retry.backoff_for(5), Duration::from_secs(1));
+    }
+
     #[tokio::test]
     async fn short_v14_redo() {
         let expected = std::fs::read("test_data/short_v14_redo.page").unwrap();
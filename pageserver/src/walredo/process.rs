@@ -0,0 +1,198 @@
+//! Process lifecycle and IPC transport for a single `wal-redo-postgres` child.
+//!
+//! The child is wrapped in [`tokio::process::Child`] rather than
+//! [`std::process::Child`] so both killing and reaping it are async and go
+//! through Tokio's own SIGCHLD-driven reaper. That matters on [`Drop`]: the
+//! dropping side sends the kill signal synchronously (a plain, non-blocking
+//! `kill()` syscall) but hands the actual `wait()` off to a detached task
+//! instead of blocking the dropping thread inside `waitpid()`.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::Context;
+use bytes::{Bytes, BytesMut};
+use pageserver_api::reltag::RelTag;
+use pageserver_api::shard::TenantShardId;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+use tokio::sync::Mutex as AsyncMutex;
+use tracing::*;
+
+use super::GlobalState;
+use crate::walrecord::NeonWalRecord;
+
+/// Size of a Postgres page image, as returned by the wal-redo process.
+const BLCKSZ: usize = 8192;
+
+pub(crate) enum LaunchError {
+    /// The manager is shutting down; don't bother spawning.
+    Cancelled,
+    Other(anyhow::Error),
+}
+
+/// A running `wal-redo-postgres` process and its stdin/stdout pipes.
+///
+/// Cheap to check out of the pool and share: callers hold an `Arc<Self>` for
+/// the duration of one `apply_wal_records` call and return it to the pool
+/// (or let it drop) afterwards.
+pub(crate) struct WalRedoProcess {
+    pid: u32,
+    tenant_shard_id: TenantShardId,
+    pg_version: u32,
+    // `None` only after `Drop` has taken it to kill + reap it.
+    child: Option<Child>,
+    stdin: AsyncMutex<ChildStdin>,
+    stdout: AsyncMutex<BufReader<ChildStdout>>,
+}
+
+impl WalRedoProcess {
+    /// Launch a new wal-redo-postgres process for `pg_version`.
+    ///
+    /// This only performs the `spawn()` syscall, which doesn't block on the
+    /// process becoming ready; it's deliberately not async so callers can
+    /// decide for themselves whether to hold a permit/lock across it.
+    pub(crate) fn launch(
+        global_state: &GlobalState,
+        tenant_shard_id: TenantShardId,
+        pg_version: u32,
+    ) -> Result<Self, LaunchError> {
+        if global_state.shutdown.is_cancelled() {
+            return Err(LaunchError::Cancelled);
+        }
+        let _guard = global_state
+            .spawn_gate
+            .enter()
+            .map_err(|_| LaunchError::Cancelled)?;
+
+        let mut child = tokio::process::Command::new("wal-redo-postgres")
+            .arg(pg_version.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            // We reap explicitly in `Drop`, asynchronously; don't let Tokio
+            // additionally try to kill-and-reap on the internal `Child` being
+            // dropped before we get there.
+            .kill_on_drop(false)
+            .spawn()
+            .with_context(|| format!("spawn wal-redo-postgres for pg_version {pg_version}"))
+            .map_err(LaunchError::Other)?;
+
+        let pid = child.id().expect("just spawned, id() is populated");
+        let stdin = child.stdin.take().expect("piped above");
+        let stdout = child.stdout.take().expect("piped above");
+
+        Ok(WalRedoProcess {
+            pid,
+            tenant_shard_id,
+            pg_version,
+            child: Some(child),
+            stdin: AsyncMutex::new(stdin),
+            stdout: AsyncMutex::new(BufReader::new(stdout)),
+        })
+    }
+
+    pub(crate) fn id(&self) -> u32 {
+        self.pid
+    }
+
+    /// Apply `records` on top of `base_img` (or a zero page, if `None`) at
+    /// `(rel, blknum)`, returning the resulting page image.
+    ///
+    /// Cancellation safe: if the returned future is dropped before
+    /// completing, the in-flight request is simply abandoned along with the
+    /// process (the caller is expected to not return a process to the pool
+    /// after a failed or cancelled call).
+    pub(crate) async fn apply_wal_records(
+        &self,
+        rel: RelTag,
+        blknum: u32,
+        base_img: &Option<Bytes>,
+        records: &[(utils::lsn::Lsn, NeonWalRecord)],
+        timeout: Duration,
+    ) -> anyhow::Result<Bytes> {
+        tokio::time::timeout(timeout, self.apply_wal_records_locked(rel, blknum, base_img, records))
+            .await
+            .context("wal-redo process timed out")?
+    }
+
+    async fn apply_wal_records_locked(
+        &self,
+        rel: RelTag,
+        blknum: u32,
+        base_img: &Option<Bytes>,
+        records: &[(utils::lsn::Lsn, NeonWalRecord)],
+    ) -> anyhow::Result<Bytes> {
+        let mut request = BytesMut::new();
+        request.extend_from_slice(&rel.spcnode.to_be_bytes());
+        request.extend_from_slice(&rel.dbnode.to_be_bytes());
+        request.extend_from_slice(&rel.relnode.to_be_bytes());
+        request.extend_from_slice(&rel.forknum.to_be_bytes());
+        request.extend_from_slice(&blknum.to_be_bytes());
+        match base_img {
+            Some(img) => {
+                request.extend_from_slice(&1u8.to_be_bytes());
+                request.extend_from_slice(img);
+            }
+            None => request.extend_from_slice(&0u8.to_be_bytes()),
+        }
+        request.extend_from_slice(&(records.len() as u32).to_be_bytes());
+        for (lsn, record) in records {
+            let NeonWalRecord::Postgres { will_init, rec } = record else {
+                unreachable!("Only PostgreSQL records are accepted in this batch");
+            };
+            request.extend_from_slice(&lsn.0.to_be_bytes());
+            request.extend_from_slice(&(*will_init as u8).to_be_bytes());
+            request.extend_from_slice(&(rec.len() as u32).to_be_bytes());
+            request.extend_from_slice(rec);
+        }
+
+        let mut stdin = self.stdin.lock().await;
+        stdin
+            .write_all(&request)
+            .await
+            .context("write to wal-redo process")?;
+        stdin.flush().await.context("flush wal-redo process stdin")?;
+        drop(stdin);
+
+        let mut page = BytesMut::zeroed(BLCKSZ);
+        let mut stdout = self.stdout.lock().await;
+        stdout
+            .read_exact(&mut page)
+            .await
+            .context("read page image from wal-redo process")?;
+        Ok(page.freeze())
+    }
+}
+
+impl Drop for WalRedoProcess {
+    fn drop(&mut self) {
+        let Some(mut child) = self.child.take() else {
+            return;
+        };
+        // Only issues the kill signal; never blocks on `wait()`.
+        if let Err(e) = child.start_kill() {
+            warn!(
+                pid = self.pid,
+                tenant_shard_id = ?self.tenant_shard_id,
+                pg_version = self.pg_version,
+                "failed to kill walredo process: {e}"
+            );
+            return;
+        }
+        let pid = self.pid;
+        let tenant_shard_id = self.tenant_shard_id;
+        // Reap asynchronously, off the dropping thread: the whole point of
+        // building on `tokio::process::Child` is that this `wait()` goes
+        // through Tokio's SIGCHLD-driven reaper instead of a blocking
+        // `waitpid()` syscall on whatever thread happened to drop the `Arc`.
+        tokio::spawn(async move {
+            match child.wait().await {
+                Ok(status) => {
+                    debug!(pid, ?tenant_shard_id, %status, "reaped walredo process")
+                }
+                Err(e) => warn!(pid, ?tenant_shard_id, "failed to reap walredo process: {e}"),
+            }
+        });
+    }
+}
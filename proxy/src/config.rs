@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use crate::console::locks::ApiLocks;
+use crate::Host;
+
+/// Retry policy for a single connect-to-compute attempt.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryConfig {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub backoff_factor: f64,
+}
+
+pub struct AuthenticationConfig {
+    pub thread_pool: std::sync::Arc<scram::ThreadPool>,
+}
+
+pub struct ProxyConfig {
+    pub auth_backend: Option<()>,
+    pub connect_compute_locks: ApiLocks<Host>,
+    pub wake_compute_retry_config: RetryConfig,
+    pub connect_to_compute_retry_config: RetryConfig,
+    /// RFC 8305 "Connection Attempt Delay" for the h2/local-proxy path. Falls
+    /// back to [`crate::serverless::backend::DEFAULT_CONNECTION_ATTEMPT_DELAY`]
+    /// when unset.
+    pub connection_attempt_delay: Option<Duration>,
+    /// Retry budget for the h2/local-proxy connect path, kept separate from
+    /// [`Self::connect_to_compute_retry_config`] since the two paths fail
+    /// differently.
+    pub http_connect_to_compute_retry_config: RetryConfig,
+}
@@ -0,0 +1,77 @@
+use once_cell::sync::Lazy;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+
+use crate::intern::EndpointIdInt;
+use crate::serverless::accounting::EndpointCountersSnapshot;
+
+/// What a latency timer is currently waiting on.
+#[derive(Debug, Clone, Copy)]
+pub enum Waiting {
+    Compute,
+}
+
+/// Per-endpoint connection accounting, exported as Prometheus counters.
+pub struct EndpointAccountingMetrics {
+    new_compute_connections: IntCounterVec,
+    pool_hits: IntCounterVec,
+    auth_success: IntCounterVec,
+    auth_failure: IntCounterVec,
+    rate_limited: IntCounterVec,
+}
+
+impl EndpointAccountingMetrics {
+    /// Add a flush-interval snapshot for `endpoint` to the exported counters.
+    pub fn observe(&self, endpoint: EndpointIdInt, snapshot: &EndpointCountersSnapshot) {
+        let label = endpoint.to_string();
+        self.new_compute_connections
+            .with_label_values(&[&label])
+            .inc_by(snapshot.new_compute_connections);
+        self.pool_hits
+            .with_label_values(&[&label])
+            .inc_by(snapshot.pool_hits);
+        self.auth_success
+            .with_label_values(&[&label])
+            .inc_by(snapshot.auth_success);
+        self.auth_failure
+            .with_label_values(&[&label])
+            .inc_by(snapshot.auth_failure);
+        self.rate_limited
+            .with_label_values(&[&label])
+            .inc_by(snapshot.rate_limited);
+    }
+}
+
+pub static ENDPOINT_ACCOUNTING: Lazy<EndpointAccountingMetrics> = Lazy::new(|| {
+    EndpointAccountingMetrics {
+        new_compute_connections: register_int_counter_vec!(
+            "proxy_endpoint_new_compute_connections_total",
+            "Number of new compute connections opened, by endpoint",
+            &["endpoint"]
+        )
+        .unwrap(),
+        pool_hits: register_int_counter_vec!(
+            "proxy_endpoint_pool_hits_total",
+            "Number of pooled connections reused, by endpoint",
+            &["endpoint"]
+        )
+        .unwrap(),
+        auth_success: register_int_counter_vec!(
+            "proxy_endpoint_auth_success_total",
+            "Number of successful auths, by endpoint",
+            &["endpoint"]
+        )
+        .unwrap(),
+        auth_failure: register_int_counter_vec!(
+            "proxy_endpoint_auth_failure_total",
+            "Number of failed auths, by endpoint",
+            &["endpoint"]
+        )
+        .unwrap(),
+        rate_limited: register_int_counter_vec!(
+            "proxy_endpoint_rate_limited_total",
+            "Number of rate-limited requests, by endpoint",
+            &["endpoint"]
+        )
+        .unwrap(),
+    }
+});
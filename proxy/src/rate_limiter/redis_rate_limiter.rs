@@ -0,0 +1,232 @@
+//! A distributed, Redis-backed token-bucket rate limiter.
+//!
+//! A fleet of proxy instances each enforcing a purely in-process limit
+//! multiplies the effective per-endpoint limit by the instance count. This
+//! limiter moves the bucket state into Redis so every instance shares one
+//! counter. The refill/consume step runs as an atomic Lua script returning
+//! either [`Outcome::Allowed`] or [`Outcome::RetryAt`] with the server-side
+//! instant at which the next token becomes available, so callers can surface a
+//! `Retry-After` hint.
+//!
+//! If Redis is unreachable we fall back to the local limiter rather than
+//! hard-failing auth on a cache outage — see [`DistributedRateLimiter`].
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use arc_swap::ArcSwapOption;
+use tokio::time::Instant as TokioInstant;
+use tracing::warn;
+
+use crate::intern::EndpointIdInt;
+use crate::rate_limiter::EndpointRateLimiter;
+use crate::redis::connection_with_credentials_provider::ConnectionWithCredentialsProvider;
+
+/// Atomic token-bucket refill + consume.
+///
+/// `KEYS[1]` is the bucket key. `ARGV` is `[now_ms, rate_per_sec, burst, cost]`.
+/// Returns `{1, 0}` when the request is allowed, or `{0, retry_after_ms}` when
+/// it is throttled. The bucket is stored as two fields: the current token count
+/// and the millisecond timestamp it was last refilled. The key is given a TTL
+/// equal to the time to refill a full bucket, so idle endpoints evict
+/// themselves.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local key   = KEYS[1]
+local now   = tonumber(ARGV[1])
+local rate  = tonumber(ARGV[2])
+local burst = tonumber(ARGV[3])
+local cost  = tonumber(ARGV[4])
+
+local data    = redis.call('HMGET', key, 'tokens', 'ts')
+local tokens  = tonumber(data[1])
+local last    = tonumber(data[2])
+if tokens == nil then
+    tokens = burst
+    last = now
+end
+
+-- Refill based on elapsed time.
+local elapsed = math.max(0, now - last) / 1000.0
+tokens = math.min(burst, tokens + elapsed * rate)
+
+if tokens >= cost then
+    tokens = tokens - cost
+    redis.call('HMSET', key, 'tokens', tokens, 'ts', now)
+    redis.call('PEXPIRE', key, math.ceil(burst / rate * 1000))
+    return {1, 0}
+else
+    -- Milliseconds until `cost` tokens are available.
+    local deficit = cost - tokens
+    local retry_ms = math.ceil(deficit / rate * 1000)
+    redis.call('HMSET', key, 'tokens', tokens, 'ts', now)
+    redis.call('PEXPIRE', key, math.ceil(burst / rate * 1000))
+    return {0, retry_ms}
+end
+"#;
+
+/// The decision returned by the limiter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The request may proceed.
+    Allowed,
+    /// The request is throttled; retry no sooner than this instant.
+    RetryAt(Instant),
+}
+
+/// Configuration for the Redis token bucket.
+#[derive(Debug, Clone, Copy)]
+pub struct RedisRateLimiterConfig {
+    /// Sustained refill rate, in tokens per second.
+    pub rate_per_sec: f64,
+    /// Maximum bucket size (burst capacity).
+    pub burst: f64,
+}
+
+/// A token bucket whose state lives in Redis.
+pub struct RedisRateLimiter {
+    client: ConnectionWithCredentialsProvider,
+    /// The `SCRIPT LOAD`ed SHA of [`TOKEN_BUCKET_SCRIPT`], cached so steady
+    /// state only pays for `EVALSHA`. Cleared and reloaded on a `NOSCRIPT`
+    /// reply (e.g. after a Redis restart or an operator-issued `SCRIPT
+    /// FLUSH`), so a one-off cache miss doesn't wedge every future call behind
+    /// the local fallback.
+    script_hash: ArcSwapOption<String>,
+    config: RedisRateLimiterConfig,
+}
+
+impl RedisRateLimiter {
+    pub fn new(
+        client: ConnectionWithCredentialsProvider,
+        config: RedisRateLimiterConfig,
+    ) -> Self {
+        RedisRateLimiter {
+            client,
+            script_hash: ArcSwapOption::empty(),
+            config,
+        }
+    }
+
+    /// `SCRIPT LOAD`s [`TOKEN_BUCKET_SCRIPT`] and caches the returned SHA,
+    /// overwriting any previously-cached (now stale) one.
+    async fn load_script(
+        &self,
+        conn: &mut redis::aio::MultiplexedConnection,
+    ) -> anyhow::Result<Arc<String>> {
+        let sha: String = redis::cmd("SCRIPT")
+            .arg("LOAD")
+            .arg(TOKEN_BUCKET_SCRIPT)
+            .query_async(conn)
+            .await?;
+        let sha = Arc::new(sha);
+        self.script_hash.store(Some(sha.clone()));
+        Ok(sha)
+    }
+
+    /// The cached script SHA, loading it for the first time if necessary.
+    async fn script_sha(
+        &self,
+        conn: &mut redis::aio::MultiplexedConnection,
+    ) -> anyhow::Result<Arc<String>> {
+        match self.script_hash.load_full() {
+            Some(sha) => Ok(sha),
+            None => self.load_script(conn).await,
+        }
+    }
+
+    async fn eval(
+        &self,
+        conn: &mut redis::aio::MultiplexedConnection,
+        sha: &str,
+        key: &str,
+        now_ms: u128,
+        cost: u32,
+    ) -> redis::RedisResult<(i64, i64)> {
+        redis::cmd("EVALSHA")
+            .arg(sha)
+            .arg(1)
+            .arg(key)
+            .arg(now_ms)
+            .arg(self.config.rate_per_sec)
+            .arg(self.config.burst)
+            .arg(cost)
+            .query_async(conn)
+            .await
+    }
+
+    /// Run the atomic refill/consume script for `endpoint`, charging `cost`
+    /// tokens. Returns an error only when Redis itself is unreachable, so the
+    /// caller can decide whether to fall back.
+    pub async fn check(
+        &self,
+        endpoint: EndpointIdInt,
+        cost: u32,
+    ) -> anyhow::Result<Outcome> {
+        let mut conn = self.client.get().await?;
+        let sha = self.script_sha(&mut conn).await?;
+
+        let now_ms = now_unix_millis();
+        let key = format!("eprl:{endpoint}");
+        let res = match self.eval(&mut conn, &sha, &key, now_ms, cost).await {
+            Ok(res) => res,
+            Err(e) if e.code() == Some("NOSCRIPT") => {
+                // The Lua script isn't in the server's cache under the SHA we
+                // have (first run against a fresh Redis, or it was flushed).
+                // Reload it and retry exactly once before giving up.
+                warn!("redis rate limiter: script cache miss (NOSCRIPT), reloading");
+                let sha = self.load_script(&mut conn).await?;
+                self.eval(&mut conn, &sha, &key, now_ms, cost).await?
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(match res {
+            (1, _) => Outcome::Allowed,
+            (_, retry_ms) => {
+                let retry_after = Duration::from_millis(retry_ms.max(0) as u64);
+                Outcome::RetryAt(Instant::now() + retry_after)
+            }
+        })
+    }
+}
+
+fn now_unix_millis() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// Wraps the distributed limiter with a local fallback so auth never hard-fails
+/// when Redis is down. Exposes the same `check`-shaped entry point as
+/// [`EndpointRateLimiter`] but additionally returns a retry-after hint.
+pub struct DistributedRateLimiter {
+    redis: Option<RedisRateLimiter>,
+    local: Arc<EndpointRateLimiter>,
+}
+
+impl DistributedRateLimiter {
+    pub fn new(redis: Option<RedisRateLimiter>, local: Arc<EndpointRateLimiter>) -> Self {
+        DistributedRateLimiter { redis, local }
+    }
+
+    /// Returns [`Outcome::Allowed`] or [`Outcome::RetryAt`]. When the
+    /// distributed backend is unavailable we degrade to the in-process limiter.
+    pub async fn check(&self, endpoint: EndpointIdInt, cost: u32) -> Outcome {
+        if let Some(redis) = &self.redis {
+            match redis.check(endpoint, cost).await {
+                Ok(outcome) => return outcome,
+                Err(e) => {
+                    warn!("distributed rate limiter unavailable, falling back to local: {e}");
+                }
+            }
+        }
+
+        if self.local.check(endpoint, cost) {
+            Outcome::Allowed
+        } else {
+            // The local limiter doesn't expose a retry instant; use a coarse
+            // estimate of one refill interval.
+            Outcome::RetryAt(TokioInstant::now().into_std() + Duration::from_secs(1))
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Per-endpoint connection accounting.
+//!
+//! The interesting per-endpoint events — new compute connections vs. pool
+//! hits, auth successes/failures, rate-limit rejections — are aggregated here
+//! into a concurrent, sharded [`DashMap`] keyed by [`EndpointIdInt`] and
+//! periodically flushed to the Prometheus exporter. The drained
+//! [`EndpointCountersSnapshot`]s are serializable, but nothing in this module
+//! writes them to the usage `Location` sink.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+
+use crate::intern::EndpointIdInt;
+
+/// The accounting counters tracked for a single endpoint. Atomics let them be
+/// bumped and drained through shared references: `record` reaches them via
+/// `DashMap::entry`, which takes the shard's write lock, and the flush drains
+/// them under the read lock.
+#[derive(Debug, Default)]
+pub struct EndpointCounters {
+    pub new_compute_connections: AtomicU64,
+    pub pool_hits: AtomicU64,
+    pub auth_success: AtomicU64,
+    pub auth_failure: AtomicU64,
+    pub rate_limited: AtomicU64,
+}
+
+/// A plain, serializable snapshot of [`EndpointCounters`] taken at flush time.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct EndpointCountersSnapshot {
+    pub new_compute_connections: u64,
+    pub pool_hits: u64,
+    pub auth_success: u64,
+    pub auth_failure: u64,
+    pub rate_limited: u64,
+}
+
+/// Which counter an event increments.
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    NewComputeConnection,
+    PoolHit,
+    AuthSuccess,
+    AuthFailure,
+    RateLimited,
+}
+
+/// Concurrent per-endpoint accounting aggregator.
+pub struct EndpointAccounting {
+    inner: DashMap<EndpointIdInt, EndpointCounters>,
+}
+
+impl EndpointAccounting {
+    pub fn new() -> Arc<Self> {
+        Arc::new(EndpointAccounting {
+            inner: DashMap::new(),
+        })
+    }
+
+    /// Record a single event for `endpoint`.
+    pub fn record(&self, endpoint: EndpointIdInt, event: Event) {
+        let entry = self.inner.entry(endpoint).or_default();
+        let counter = match event {
+            Event::NewComputeConnection => &entry.new_compute_connections,
+            Event::PoolHit => &entry.pool_hits,
+            Event::AuthSuccess => &entry.auth_success,
+            Event::AuthFailure => &entry.auth_failure,
+            Event::RateLimited => &entry.rate_limited,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Drain the current counters into a plain snapshot map, resetting the live
+    /// counters to zero. Called each flush interval.
+    pub fn drain_snapshot(&self) -> Vec<(EndpointIdInt, EndpointCountersSnapshot)> {
+        let mut out = Vec::with_capacity(self.inner.len());
+        let mut idle = Vec::new();
+        for entry in self.inner.iter() {
+            let c = entry.value();
+            let snapshot = EndpointCountersSnapshot {
+                new_compute_connections: c.new_compute_connections.swap(0, Ordering::Relaxed),
+                pool_hits: c.pool_hits.swap(0, Ordering::Relaxed),
+                auth_success: c.auth_success.swap(0, Ordering::Relaxed),
+                auth_failure: c.auth_failure.swap(0, Ordering::Relaxed),
+                rate_limited: c.rate_limited.swap(0, Ordering::Relaxed),
+            };
+            // Decide idleness from the values we just captured, not from the
+            // atomics afterward (those are now zeroed by the swaps above).
+            let had_activity = snapshot.new_compute_connections != 0
+                || snapshot.pool_hits != 0
+                || snapshot.auth_success != 0
+                || snapshot.auth_failure != 0
+                || snapshot.rate_limited != 0;
+            if !had_activity {
+                idle.push(*entry.key());
+            }
+            out.push((*entry.key(), snapshot));
+        }
+        // Drop endpoints that saw no activity this window to bound map growth.
+        for endpoint in idle {
+            self.inner.remove(&endpoint);
+        }
+        out
+    }
+
+    /// Spawn a background task that flushes the counters to the metrics exporter
+    /// every `interval`.
+    pub fn spawn_flush_task(self: &Arc<Self>, interval: Duration) {
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                for (endpoint, snapshot) in this.drain_snapshot() {
+                    crate::metrics::ENDPOINT_ACCOUNTING.observe(endpoint, &snapshot);
+                }
+            }
+        });
+    }
+}
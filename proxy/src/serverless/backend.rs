@@ -1,13 +1,20 @@
+use std::net::SocketAddr;
 use std::{io, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
 use http_body_util::Full;
 use hyper1::client::conn::http2;
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use tokio::net::{lookup_host, TcpStream};
 use tracing::{field::display, info};
 
+/// RFC 8305 "Connection Attempt Delay": how long to wait before starting the
+/// next staggered connection attempt while earlier ones are still in flight.
+const DEFAULT_CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
 use crate::{
     auth::{
         backend::{local::StaticAuthRules, ComputeCredentials, ComputeUserInfo},
@@ -28,11 +35,15 @@ use crate::{
         connect_compute::ConnectMechanism,
         retry::{CouldRetry, ShouldRetryWakeCompute},
     },
-    rate_limiter::EndpointRateLimiter,
+    rate_limiter::{
+        redis_rate_limiter::{self, DistributedRateLimiter},
+        EndpointRateLimiter,
+    },
     Host,
 };
 
 use super::{
+    accounting::{EndpointAccounting, Event},
     conn_pool::{poll_client, Client, ConnInfo, GlobalConnPool},
     http_conn_pool::{self, poll_http2_client},
 };
@@ -42,6 +53,11 @@ pub(crate) struct PoolingBackend {
     pub(crate) pool: Arc<GlobalConnPool<tokio_postgres::Client>>,
     pub(crate) config: &'static ProxyConfig,
     pub(crate) endpoint_rate_limiter: Arc<EndpointRateLimiter>,
+    /// Fleet-wide, Redis-backed per-endpoint limiter. When set it supersedes the
+    /// in-process `endpoint_rate_limiter`, falling back to it on a cache outage.
+    pub(crate) distributed_rate_limiter: Option<Arc<DistributedRateLimiter>>,
+    /// Per-endpoint connection/auth accounting flushed to metrics.
+    pub(crate) accounting: Arc<EndpointAccounting>,
 }
 
 impl PoolingBackend {
@@ -64,10 +80,18 @@ impl PoolingBackend {
         {
             return Err(AuthError::ip_address_not_allowed(ctx.peer_addr()));
         }
-        if !self
-            .endpoint_rate_limiter
-            .check(user_info.endpoint.clone().into(), 1)
-        {
+        // Enforce the per-endpoint limit. When a distributed (Redis-backed)
+        // limiter is configured it shares state across the whole fleet and
+        // returns a server-side retry-after; otherwise we fall back to the
+        // in-process limiter.
+        let ep = EndpointIdInt::from(&user_info.endpoint);
+        if let Some(distributed) = &self.distributed_rate_limiter {
+            if let redis_rate_limiter::Outcome::RetryAt(at) = distributed.check(ep, 1).await {
+                self.accounting.record(ep, Event::RateLimited);
+                return Err(AuthError::too_many_connections().with_retry_at(at));
+            }
+        } else if !self.endpoint_rate_limiter.check(ep, 1) {
+            self.accounting.record(ep, Event::RateLimited);
             return Err(AuthError::too_many_connections());
         }
         let cached_secret = match maybe_secret {
@@ -89,17 +113,18 @@ impl PoolingBackend {
                 return Err(AuthError::auth_failed(&*user_info.user));
             }
         };
-        let ep = EndpointIdInt::from(&user_info.endpoint);
         let auth_outcome =
             crate::auth::validate_password_and_exchange(&config.thread_pool, ep, password, secret)
                 .await?;
         let res = match auth_outcome {
             crate::sasl::Outcome::Success(key) => {
                 info!("user successfully authenticated");
+                self.accounting.record(ep, Event::AuthSuccess);
                 Ok(key)
             }
             crate::sasl::Outcome::Failure(reason) => {
                 info!("auth backend failed with an error: {reason}");
+                self.accounting.record(ep, Event::AuthFailure);
                 Err(AuthError::auth_failed(&*user_info.user))
             }
         };
@@ -177,9 +202,12 @@ impl PoolingBackend {
             self.pool.get(ctx, &conn_info)?
         };
 
+        let ep = EndpointIdInt::from(&conn_info.user_info.endpoint);
         if let Some(client) = maybe_client {
+            self.accounting.record(ep, Event::PoolHit);
             return Ok(client);
         }
+        self.accounting.record(ep, Event::NewComputeConnection);
         let conn_id = uuid::Uuid::new_v4();
         tracing::Span::current().record("conn_id", display(conn_id));
         info!(%conn_id, "pool: opening a new connection '{conn_info}'");
@@ -209,9 +237,12 @@ impl PoolingBackend {
         keys: ComputeCredentials,
     ) -> Result<http_conn_pool::Client, HttpConnError> {
         info!("pool: looking for an existing connection");
+        let ep = EndpointIdInt::from(&conn_info.user_info.endpoint);
         if let Some(client) = self.http_conn_pool.get(ctx, &conn_info) {
+            self.accounting.record(ep, Event::PoolHit);
             return Ok(client);
         }
+        self.accounting.record(ep, Event::NewComputeConnection);
 
         let conn_id = uuid::Uuid::new_v4();
         tracing::Span::current().record("conn_id", display(conn_id));
@@ -224,11 +255,17 @@ impl PoolingBackend {
                 conn_info,
                 pool: self.http_conn_pool.clone(),
                 locks: &self.config.connect_compute_locks,
+                connection_attempt_delay: self
+                    .config
+                    .connection_attempt_delay
+                    .unwrap_or(DEFAULT_CONNECTION_ATTEMPT_DELAY),
             },
             &backend,
             false, // do not allow self signed compute for http flow
             self.config.wake_compute_retry_config,
-            self.config.connect_to_compute_retry_config,
+            // The h2/local-proxy path has its own retry budget so it doesn't
+            // inherit the Postgres-path behavior verbatim.
+            self.config.http_connect_to_compute_retry_config,
         )
         .await
     }
@@ -287,12 +324,31 @@ impl UserFacingError for HttpConnError {
     }
 }
 
+/// Whether an IO error seen while *establishing* a connection is transient and
+/// worth retrying on a fresh socket (connection refused/reset/timed out/aborted
+/// typically indicate a compute that is still waking or a stale pooled socket).
+fn io_connect_error_is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::NotConnected
+    )
+}
+
 impl CouldRetry for HttpConnError {
     fn could_retry(&self) -> bool {
         match self {
             HttpConnError::ConnectionError(e) => e.could_retry(),
-            HttpConnError::IoConnectionError(e) => e.could_retry(),
-            HttpConnError::H2ConnectionError(_) => false,
+            HttpConnError::IoConnectionError(e) => io_connect_error_is_transient(e),
+            // A GOAWAY or failed handshake on a freshly-dialed socket is a
+            // connection-establishment failure, not a request failure: the
+            // request was never sent, so retrying on a new connection is safe.
+            HttpConnError::H2ConnectionError(e) => e.is_canceled() || e.is_incomplete_message(),
             HttpConnError::ConnectionClosedAbruptly(_) => false,
             HttpConnError::GetAuthInfo(_) => false,
             HttpConnError::AuthError(_) => false,
@@ -369,6 +425,9 @@ struct HyperMechanism {
 
     /// connect_to_compute concurrency lock
     locks: &'static ApiLocks<Host>,
+
+    /// RFC 8305 Connection Attempt Delay used when racing dual-stack addresses.
+    connection_attempt_delay: Duration,
 }
 
 #[async_trait]
@@ -389,7 +448,7 @@ impl ConnectMechanism for HyperMechanism {
         let pause = ctx.latency_timer_pause(crate::metrics::Waiting::Compute);
 
         // let port = node_info.config.get_ports().first().unwrap_or_else(10432);
-        let res = connect_http2(&host, 10432, timeout).await;
+        let res = connect_http2(&host, 10432, timeout, self.connection_attempt_delay).await;
         drop(pause);
         let (client, connection) = permit.release_result(res)?;
 
@@ -407,10 +466,49 @@ impl ConnectMechanism for HyperMechanism {
     fn update_connect_config(&self, _config: &mut compute::ConnCfg) {}
 }
 
+/// Reorder resolved addresses per RFC 8305: interleave address families so we
+/// alternate between them, preferring IPv6 (AAAA) first. This avoids paying the
+/// full timeout on a run of dead addresses from a single family.
+fn interleave_by_family(addrs: impl Iterator<Item = SocketAddr>) -> Vec<SocketAddr> {
+    let (mut v6, mut v4): (Vec<_>, Vec<_>) = addrs.partition(SocketAddr::is_ipv6);
+    let mut v6 = v6.drain(..);
+    let mut v4 = v4.drain(..);
+    let mut ordered = Vec::new();
+    loop {
+        match (v6.next(), v4.next()) {
+            (Some(a), Some(b)) => {
+                ordered.push(a);
+                ordered.push(b);
+            }
+            (Some(a), None) => ordered.push(a),
+            (None, Some(b)) => ordered.push(b),
+            (None, None) => break,
+        }
+    }
+    ordered
+}
+
+/// Open a single TCP stream with `set_nodelay`, mapping timeouts to IO errors.
+async fn connect_one(addr: SocketAddr, timeout: Duration) -> Result<TcpStream, HttpConnError> {
+    let stream = match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => return Err(HttpConnError::IoConnectionError(e)),
+        Err(e) => {
+            return Err(HttpConnError::IoConnectionError(io::Error::new(
+                io::ErrorKind::TimedOut,
+                e,
+            )));
+        }
+    };
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
 async fn connect_http2(
     host: &str,
     port: u16,
     timeout: Duration,
+    connection_attempt_delay: Duration,
 ) -> Result<
     (
         http2::SendRequest<Full<Bytes>>,
@@ -418,38 +516,72 @@ async fn connect_http2(
     ),
     HttpConnError,
 > {
-    let mut addrs = lookup_host((host, port)).await?;
+    let addrs = interleave_by_family(lookup_host((host, port)).await?);
+    if addrs.is_empty() {
+        return Err(HttpConnError::IoConnectionError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve any addresses",
+        )));
+    }
+
+    // RFC 8305 connection racing: stagger attempts by `connection_attempt_delay`
+    // rather than firing them all at once or strictly sequentially. The first
+    // socket to connect wins; the rest are dropped (aborted) when `in_flight`
+    // goes out of scope.
+    let overall = tokio::time::sleep(timeout);
+    tokio::pin!(overall);
 
+    let mut addrs = addrs.into_iter();
+    let mut in_flight = FuturesUnordered::new();
     let mut last_err = None;
 
-    let stream = loop {
-        let Some(addr) = addrs.next() else {
-            return Err(last_err.unwrap_or_else(|| {
-                HttpConnError::IoConnectionError(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "could not resolve any addresses",
-                ))
-            }));
-        };
+    let stream = 'outer: loop {
+        // Launch the next staggered attempt, if any remain.
+        if let Some(addr) = addrs.next() {
+            in_flight.push(connect_one(addr, timeout));
+        }
 
-        let stream = match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
-            Ok(Ok(stream)) => stream,
-            Ok(Err(e)) => {
-                last_err = Some(HttpConnError::IoConnectionError(e));
-                continue;
-            }
-            Err(e) => {
-                last_err = Some(HttpConnError::IoConnectionError(io::Error::new(
-                    io::ErrorKind::TimedOut,
-                    e,
-                )));
-                continue;
-            }
-        };
+        if in_flight.is_empty() && addrs.len() == 0 {
+            break None;
+        }
 
-        stream.set_nodelay(true)?;
+        // A short timer that lets us start the next attempt if the current ones
+        // are taking too long, as long as we still have addresses to try.
+        let stagger = tokio::time::sleep(connection_attempt_delay);
+        tokio::pin!(stagger);
+
+        loop {
+            tokio::select! {
+                biased;
+                () = &mut overall => {
+                    break 'outer None;
+                }
+                res = in_flight.next(), if !in_flight.is_empty() => {
+                    match res {
+                        Some(Ok(stream)) => break 'outer Some(stream),
+                        Some(Err(e)) => {
+                            last_err = Some(e);
+                            // Try the next address immediately on failure.
+                            continue 'outer;
+                        }
+                        None => continue 'outer,
+                    }
+                }
+                () = &mut stagger => {
+                    // Give the next address a chance to race the in-flight ones.
+                    continue 'outer;
+                }
+            }
+        }
+    };
 
-        break stream;
+    let Some(stream) = stream else {
+        return Err(last_err.unwrap_or_else(|| {
+            HttpConnError::IoConnectionError(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out connecting to compute",
+            ))
+        }));
     };
 
     let (client, connection) = hyper1::client::conn::http2::Builder::new(TokioExecutor::new())
@@ -10,9 +10,10 @@ use std::marker::PhantomPinned;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::{error::Error as StdError, io, marker::Unpin};
-use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
 
 use ::http1::{Request, Response};
+use base64::Engine;
 use bytes::Bytes;
 use hyper1::{body::Incoming, service::Service};
 
@@ -32,6 +33,9 @@ const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
 pub struct Builder {
     http1: http1::Builder,
     http2: http2::Builder<TokioExecutor>,
+    header_read_timeout: Option<std::time::Duration>,
+    max_connection_age: Option<std::time::Duration>,
+    idle_timeout: Option<std::time::Duration>,
 }
 
 impl Builder {
@@ -40,6 +44,9 @@ impl Builder {
         let mut builder = Self {
             http1: http1::Builder::new(),
             http2: http2::Builder::new(TokioExecutor::new()),
+            header_read_timeout: None,
+            max_connection_age: None,
+            idle_timeout: None,
         };
 
         builder.http1.timer(TokioTimer::new());
@@ -48,6 +55,136 @@ impl Builder {
         builder
     }
 
+    /// Set a timeout that bounds how long the connection may spend reading the
+    /// request/preface headers.
+    ///
+    /// This caps the time [`ReadVersion`] may spend sniffing the H2 preface (a
+    /// slowloris client dribbling one byte at a time would otherwise pin the
+    /// connection task indefinitely) and is also forwarded to hyper's HTTP/1
+    /// `header_read_timeout` once the H1 path is chosen. On expiry the sniffing
+    /// future resolves to an [`io::ErrorKind::TimedOut`] error so the accept
+    /// loop drops the connection.
+    pub fn header_read_timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+        self.header_read_timeout = Some(timeout);
+        self.http1.header_read_timeout(timeout);
+        self
+    }
+
+    /// Set the maximum age of a served connection. Once this elapses the
+    /// connection initiates a graceful shutdown so long-lived websocket sessions
+    /// don't pin resources forever.
+    pub fn max_connection_age(&mut self, age: std::time::Duration) -> &mut Self {
+        self.max_connection_age = Some(age);
+        self
+    }
+
+    /// Set the maximum time a served connection may make no read/write progress
+    /// before it is dropped.
+    pub fn idle_timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Start sniffing the protocol version of `io`, honoring the configured
+    /// [`header_read_timeout`](Self::header_read_timeout).
+    pub(crate) fn read_version<I>(&self, io: I) -> ReadVersion<I>
+    where
+        I: AsyncRead + Unpin,
+    {
+        read_version(io, self.header_read_timeout)
+    }
+
+    // === HTTP/1 passthrough setters ===
+    //
+    // These mirror the knobs on hyper's `http1::Builder` so operators can tune
+    // the proxy's listener instead of accepting the library defaults.
+
+    /// Set whether HTTP/1 connections should support half-closures.
+    pub fn http1_half_close(&mut self, val: bool) -> &mut Self {
+        self.http1.half_close(val);
+        self
+    }
+
+    /// Enable or disable HTTP/1 keep-alive.
+    pub fn http1_keep_alive(&mut self, val: bool) -> &mut Self {
+        self.http1.keep_alive(val);
+        self
+    }
+
+    /// Set whether HTTP/1 responses should be written in Title-Case.
+    pub fn http1_title_case_headers(&mut self, val: bool) -> &mut Self {
+        self.http1.title_case_headers(val);
+        self
+    }
+
+    /// Set whether to preserve the original case of HTTP/1 header names.
+    pub fn http1_preserve_header_case(&mut self, val: bool) -> &mut Self {
+        self.http1.preserve_header_case(val);
+        self
+    }
+
+    /// Set the maximum buffer size for the HTTP/1 connection.
+    pub fn http1_max_buf_size(&mut self, max: usize) -> &mut Self {
+        self.http1.max_buf_size(max);
+        self
+    }
+
+    /// Aggregate HTTP/1 flushes to better support pipelined responses.
+    pub fn http1_pipeline_flush(&mut self, val: bool) -> &mut Self {
+        self.http1.pipeline_flush(val);
+        self
+    }
+
+    /// Set whether HTTP/1 writes should use vectored IO.
+    pub fn http1_writev(&mut self, val: bool) -> &mut Self {
+        self.http1.writev(val);
+        self
+    }
+
+    // === HTTP/2 passthrough setters ===
+
+    /// Set the initial HTTP/2 stream-level flow control window size.
+    pub fn http2_initial_stream_window_size(&mut self, sz: impl Into<Option<u32>>) -> &mut Self {
+        self.http2.initial_stream_window_size(sz);
+        self
+    }
+
+    /// Set the initial HTTP/2 connection-level flow control window size.
+    pub fn http2_initial_connection_window_size(
+        &mut self,
+        sz: impl Into<Option<u32>>,
+    ) -> &mut Self {
+        self.http2.initial_connection_window_size(sz);
+        self
+    }
+
+    /// Set the maximum number of concurrent HTTP/2 streams per connection.
+    pub fn http2_max_concurrent_streams(&mut self, max: impl Into<Option<u32>>) -> &mut Self {
+        self.http2.max_concurrent_streams(max);
+        self
+    }
+
+    /// Set the maximum HTTP/2 frame size to use.
+    pub fn http2_max_frame_size(&mut self, sz: impl Into<Option<u32>>) -> &mut Self {
+        self.http2.max_frame_size(sz);
+        self
+    }
+
+    /// Set the HTTP/2 keep-alive ping interval.
+    pub fn http2_keep_alive_interval(
+        &mut self,
+        interval: impl Into<Option<std::time::Duration>>,
+    ) -> &mut Self {
+        self.http2.keep_alive_interval(interval);
+        self
+    }
+
+    /// Set the HTTP/2 keep-alive ping timeout.
+    pub fn http2_keep_alive_timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+        self.http2.keep_alive_timeout(timeout);
+        self
+    }
+
     /// Bind a connection together with a [`Service`], with the ability to
     /// handle HTTP upgrades. This requires that the IO object implements
     /// `Send`.
@@ -66,41 +203,100 @@ impl Builder {
         I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
         TokioExecutor: Http2ServerConnExec<S::Future, B>,
     {
-        match version {
+        // Share an activity flag with the `Rewind` IO so the idle timer can tell
+        // when the connection last made progress.
+        let activity = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let io = io.with_activity(activity.clone());
+
+        let lifecycle = Lifecycle {
+            age: self
+                .max_connection_age
+                .map(|d| Box::pin(tokio::time::sleep(d))),
+            idle: self.idle_timeout.map(|d| Box::pin(tokio::time::sleep(d))),
+            idle_timeout: self.idle_timeout,
+            activity,
+            shutting_down: false,
+        };
+
+        let state = match version {
             Version::H1 => {
                 let conn = self
                     .http1
                     .serve_connection(TokioIo::new(io), service)
                     .with_upgrades();
-                UpgradeableConnection {
-                    state: UpgradeableConnState::H1 { conn },
-                }
+                UpgradeableConnState::H1 { conn }
             }
             Version::H2 => {
                 let conn = self.http2.serve_connection(TokioIo::new(io), service);
-                UpgradeableConnection {
-                    state: UpgradeableConnState::H2 { conn },
-                }
+                UpgradeableConnState::H2 { conn }
             }
-        }
+            Version::H2c(sniffed) => {
+                // Send the `101 Switching Protocols` handshake. The sniffed
+                // HTTP/1.1 request bytes are *not* valid HTTP/2 wire format —
+                // per RFC 7540 §3.2 that request must instead be reinterpreted
+                // as HTTP/2 stream 1. We synthesize the client connection
+                // preface plus a HEADERS frame carrying the original request
+                // and prepend that to the stream in place of the raw bytes.
+                let http2 = self.http2.clone();
+                let handshake = Box::pin(async move {
+                    let mut io = io;
+                    io.write_all(H2C_SWITCHING_PROTOCOLS).await?;
+                    io.flush().await?;
+                    let synthetic = build_h2c_stream1_preface(&sniffed)?;
+                    io.prepend(synthetic);
+                    Ok(http2.serve_connection(TokioIo::new(io), service))
+                });
+                UpgradeableConnState::H2c { handshake }
+            }
+        };
+
+        UpgradeableConnection { state, lifecycle }
     }
 }
 
-#[derive(Copy, Clone)]
+/// Per-connection lifecycle timers applied on top of the inner hyper connection.
+struct Lifecycle {
+    // Fires once, `max_connection_age` after construction; triggers a graceful
+    // shutdown of the connection.
+    age: Option<Pin<Box<tokio::time::Sleep>>>,
+    // Reset whenever the underlying IO makes progress; firing drops the conn.
+    idle: Option<Pin<Box<tokio::time::Sleep>>>,
+    idle_timeout: Option<std::time::Duration>,
+    activity: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    shutting_down: bool,
+}
+
+#[derive(Clone)]
 pub(crate) enum Version {
     H1,
     H2,
+    /// An HTTP/1.1 request asking to upgrade to cleartext HTTP/2 (`h2c`). The
+    /// server must perform the `101 Switching Protocols` handshake and then
+    /// serve the HTTP/2 path, with the carried bytes (the sniffed HTTP/1.1
+    /// request line and header block) reinterpreted as stream 1 rather than
+    /// replayed as raw wire bytes.
+    H2c(Bytes),
 }
 
-pub(crate) fn read_version<I>(io: I) -> ReadVersion<I>
+/// Upper bound on the header block we are willing to buffer while sniffing for
+/// an `h2c` upgrade. A request line + headers larger than this is served as
+/// plain H1 (hyper's own `header_read_timeout`/limits then apply).
+const MAX_SNIFFED_HEADER: usize = 16 * 1024;
+
+pub(crate) fn read_version<I>(
+    io: I,
+    header_read_timeout: Option<std::time::Duration>,
+) -> ReadVersion<I>
 where
     I: AsyncRead + Unpin,
 {
     ReadVersion {
         io: Some(io),
-        buf: [0; 24],
-        filled: 0,
+        buf: Vec::with_capacity(H2_PREFACE.len()),
         version: Version::H2,
+        sniffing_h2c: false,
+        wants_h2c: false,
+        timeout: header_read_timeout.map(|d| Box::pin(tokio::time::sleep(d))),
         _pin: PhantomPinned,
     }
 }
@@ -108,16 +304,80 @@ where
 pin_project! {
     pub(crate) struct ReadVersion<I> {
         io: Option<I>,
-        buf: [u8; 24],
-        // the amount of `buf` thats been filled
-        filled: usize,
+        // The sniffed bytes. For H1/H2 these are replayed verbatim to the
+        // winning protocol via `Rewind`. For an `h2c` upgrade they are instead
+        // handed to `build_h2c_stream1_preface` and never replayed raw — see
+        // `wants_h2c`.
+        buf: Vec<u8>,
         version: Version,
+        // Once we know it is H1, keep reading the header block to look for an
+        // `Upgrade: h2c` token before committing to a protocol.
+        sniffing_h2c: bool,
+        // Set once an `Upgrade: h2c` request is recognized. Tracked separately
+        // from `version` so `buf` keeps accumulating the full header block
+        // until the loop below is done with it.
+        wants_h2c: bool,
+        // When set, bounds the total time spent sniffing the preface; on expiry
+        // the future resolves to `io::ErrorKind::TimedOut`.
+        timeout: Option<Pin<Box<tokio::time::Sleep>>>,
         // Make this future `!Unpin` for compatibility with async trait methods.
         #[pin]
         _pin: PhantomPinned,
     }
 }
 
+/// Read one chunk into `buf`, growing it as needed. Returns the number of bytes
+/// read (0 on EOF).
+fn poll_read_into<I>(
+    io: &mut I,
+    buf: &mut Vec<u8>,
+    up_to: usize,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<usize>>
+where
+    I: AsyncRead + Unpin,
+{
+    let start = buf.len();
+    buf.resize(up_to, 0);
+    let mut read_buf = ReadBuf::new(&mut buf[start..]);
+    let res = Pin::new(io).poll_read(cx, &mut read_buf);
+    let filled = read_buf.filled().len();
+    buf.truncate(start + filled);
+    match res {
+        Poll::Ready(Ok(())) => Poll::Ready(Ok(filled)),
+        Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+        Poll::Pending => {
+            buf.truncate(start);
+            Poll::Pending
+        }
+    }
+}
+
+/// Scan an HTTP/1 header block for a `Connection: upgrade` + `Upgrade: h2c`
+/// pair, indicating the client wants to negotiate cleartext HTTP/2.
+fn wants_h2c_upgrade(headers: &[u8]) -> bool {
+    let mut has_upgrade_token = false;
+    let mut upgrades_h2c = false;
+    for line in headers.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let Some(colon) = line.iter().position(|&b| b == b':') else {
+            continue;
+        };
+        let name = std::str::from_utf8(&line[..colon]).unwrap_or("").trim();
+        let value = std::str::from_utf8(&line[colon + 1..]).unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("connection") {
+            has_upgrade_token |= value
+                .split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case("upgrade"));
+        } else if name.eq_ignore_ascii_case("upgrade") {
+            upgrades_h2c |= value
+                .split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case("h2c"));
+        }
+    }
+    has_upgrade_token && upgrades_h2c
+}
+
 impl<I> Future for ReadVersion<I>
 where
     I: AsyncRead + Unpin,
@@ -127,31 +387,198 @@ where
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
 
-        let mut buf = ReadBuf::new(&mut *this.buf);
-        buf.set_filled(*this.filled);
+        // Drop the connection if the client is too slow to send the preface.
+        if let Some(timeout) = this.timeout.as_mut() {
+            if timeout.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "timed out reading connection preface",
+                )));
+            }
+        }
 
-        // We start as H2 and switch to H1 as soon as we don't have the preface.
-        while buf.filled().len() < H2_PREFACE.len() {
-            let len = buf.filled().len();
-            ready!(Pin::new(this.io.as_mut().unwrap()).poll_read(cx, &mut buf))?;
-            *this.filled = buf.filled().len();
+        let io = this.io.as_mut().unwrap();
 
-            // We starts as H2 and switch to H1 when we don't get the preface.
-            if buf.filled().len() == len
-                || buf.filled()[len..] != H2_PREFACE[len..buf.filled().len()]
-            {
+        // Phase 1: sniff the HTTP/2 connection preface. We start assuming H2 and
+        // fall back to H1 the moment the bytes diverge from the preface.
+        while !*this.sniffing_h2c && this.buf.len() < H2_PREFACE.len() {
+            let before = this.buf.len();
+            let n = ready!(poll_read_into(io, this.buf, H2_PREFACE.len(), cx))?;
+            if n == 0 || this.buf[before..] != H2_PREFACE[before..this.buf.len()] {
                 *this.version = Version::H1;
+                *this.sniffing_h2c = true;
+                break;
+            }
+        }
+
+        // Phase 2: if this is H1, keep reading until the end of the header block
+        // so we can detect an `Upgrade: h2c` request.
+        while *this.sniffing_h2c {
+            if let Some(end) = this
+                .buf
+                .windows(4)
+                .position(|w| w == b"\r\n\r\n")
+            {
+                if wants_h2c_upgrade(&this.buf[..end]) {
+                    *this.wants_h2c = true;
+                }
+                break;
+            }
+            if this.buf.len() >= MAX_SNIFFED_HEADER {
+                break;
+            }
+            let n = ready!(poll_read_into(
+                io,
+                this.buf,
+                (this.buf.len() + 1024).min(MAX_SNIFFED_HEADER),
+                cx
+            ))?;
+            if n == 0 {
                 break;
             }
         }
 
         let io = this.io.take().unwrap();
-        let buf = buf.filled().to_vec();
-        Poll::Ready(Ok((
-            *this.version,
-            Rewind::new_buffered(io, Bytes::from(buf)),
-        )))
+        let buf = std::mem::take(this.buf);
+        let (version, rewind_prefix) = if *this.wants_h2c {
+            // The sniffed bytes become the synthetic stream 1 request built in
+            // `serve_connection_with_upgrades`, not a literal byte replay.
+            (Version::H2c(Bytes::from(buf)), Bytes::new())
+        } else {
+            (this.version.clone(), Bytes::from(buf))
+        };
+        Poll::Ready(Ok((version, Rewind::new_buffered(io, rewind_prefix))))
+    }
+}
+
+/// The `101 Switching Protocols` response sent to accept an `h2c` upgrade.
+const H2C_SWITCHING_PROTOCOLS: &[u8] =
+    b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n";
+
+/// Request headers that are either hop-by-hop or specific to negotiating the
+/// upgrade itself, and so must not be forwarded onto HTTP/2 stream 1.
+const H2C_UPGRADE_HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "upgrade",
+    "http2-settings",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "te",
+    "trailer",
+    "host",
+];
+
+/// Build the bytes to prepend to an `h2c`-upgraded connection in place of the
+/// sniffed HTTP/1.1 request, so the generic HTTP/2 server codec (which always
+/// expects a client connection preface) sees: the preface magic, a SETTINGS
+/// frame decoded from the request's `HTTP2-Settings` header, and a HEADERS
+/// frame reconstructing the original request as stream 1 (RFC 7540 §3.2).
+/// This mirrors how `golang.org/x/net/http2/h2c` bridges the same gap.
+fn build_h2c_stream1_preface(sniffed: &[u8]) -> io::Result<Bytes> {
+    let mut header_storage = [httparse::EMPTY_HEADER; 64];
+    let mut parsed = httparse::Request::new(&mut header_storage);
+    parsed
+        .parse(sniffed)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let method = parsed.method.unwrap_or("GET");
+    let path = parsed.path.unwrap_or("/");
+
+    let mut authority = "";
+    let mut settings_payload: &[u8] = &[];
+    let mut settings_buf = Vec::new();
+    for header in parsed.headers.iter() {
+        if header.name.eq_ignore_ascii_case("host") {
+            authority = std::str::from_utf8(header.value).unwrap_or("");
+        } else if header.name.eq_ignore_ascii_case("http2-settings") {
+            if let Ok(value) = std::str::from_utf8(header.value) {
+                if let Ok(decoded) = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(value)
+                {
+                    settings_buf = decoded;
+                    settings_payload = &settings_buf;
+                }
+            }
+        }
     }
+
+    let mut out = Vec::with_capacity(H2_PREFACE.len() + 9 + settings_payload.len() + 64);
+    out.extend_from_slice(H2_PREFACE);
+    write_frame_header(&mut out, settings_payload.len(), 0x4, 0x0, 0);
+    out.extend_from_slice(settings_payload);
+
+    let headers_payload = encode_stream1_headers(method, authority, path, parsed.headers);
+    // END_HEADERS | END_STREAM: an h2c-upgrade request is not expected to
+    // carry a body, matching the restriction other h2c implementations apply.
+    write_frame_header(&mut out, headers_payload.len(), 0x1, 0x4 | 0x1, 1);
+    out.extend_from_slice(&headers_payload);
+
+    Ok(Bytes::from(out))
+}
+
+fn write_frame_header(out: &mut Vec<u8>, len: usize, frame_type: u8, flags: u8, stream_id: u32) {
+    let len = len as u32;
+    out.push((len >> 16) as u8);
+    out.push((len >> 8) as u8);
+    out.push(len as u8);
+    out.push(frame_type);
+    out.push(flags);
+    out.extend_from_slice(&(stream_id & 0x7fff_ffff).to_be_bytes());
+}
+
+fn encode_stream1_headers(
+    method: &str,
+    authority: &str,
+    path: &str,
+    headers: &[httparse::Header<'_>],
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    hpack_literal_new_name(&mut out, ":method", method.as_bytes());
+    hpack_literal_new_name(&mut out, ":scheme", b"http");
+    hpack_literal_new_name(&mut out, ":authority", authority.as_bytes());
+    hpack_literal_new_name(&mut out, ":path", path.as_bytes());
+    for header in headers {
+        if H2C_UPGRADE_HOP_BY_HOP_HEADERS
+            .iter()
+            .any(|h| header.name.eq_ignore_ascii_case(h))
+        {
+            continue;
+        }
+        hpack_literal_new_name(&mut out, &header.name.to_ascii_lowercase(), header.value);
+    }
+    out
+}
+
+/// HPACK (RFC 7541) integer representation with the given prefix size.
+fn hpack_encode_int(out: &mut Vec<u8>, prefix_bits: u32, prefix_mask: u8, mut value: usize) {
+    let max_prefix = (1usize << prefix_bits) - 1;
+    if value < max_prefix {
+        out.push(prefix_mask | value as u8);
+        return;
+    }
+    out.push(prefix_mask | max_prefix as u8);
+    value -= max_prefix;
+    while value >= 128 {
+        out.push(((value % 128) | 0x80) as u8);
+        value /= 128;
+    }
+    out.push(value as u8);
+}
+
+/// HPACK string literal, without Huffman coding (the `H` bit is always 0).
+fn hpack_encode_string(out: &mut Vec<u8>, s: &[u8]) {
+    hpack_encode_int(out, 7, 0x00, s.len());
+    out.extend_from_slice(s);
+}
+
+/// HPACK "Literal Header Field without Indexing — New Name" (RFC 7541 §6.2.2).
+/// Skipping the dynamic/static table entirely keeps this self-contained at the
+/// cost of a few extra bytes on the wire, which only matters for this single
+/// synthetic frame.
+fn hpack_literal_new_name(out: &mut Vec<u8>, name: &str, value: &[u8]) {
+    out.push(0x00);
+    hpack_encode_string(out, name.as_bytes());
+    hpack_encode_string(out, value);
 }
 
 pin_project! {
@@ -162,6 +589,7 @@ pin_project! {
     {
         #[pin]
         state: UpgradeableConnState<I, S>,
+        lifecycle: Lifecycle,
     }
 }
 
@@ -169,6 +597,8 @@ type Http1UpgradeableConnection<I, S> =
     hyper1::server::conn::http1::UpgradeableConnection<TokioIo<Rewind<I>>, S>;
 type Http2Connection<I, S> =
     hyper1::server::conn::http2::Connection<TokioIo<Rewind<I>>, S, TokioExecutor>;
+type H2cHandshake<I, S> =
+    Pin<Box<dyn Future<Output = io::Result<Http2Connection<I, S>>> + Send>>;
 
 pin_project! {
     #[project = UpgradeableConnStateProj]
@@ -184,6 +614,11 @@ pin_project! {
             #[pin]
             conn: Http2Connection<I, S>,
         },
+        // An in-flight `h2c` upgrade: run the 101 handshake, then swap this
+        // state out for `H2` once the HTTP/2 connection has been constructed.
+        H2c {
+            handshake: H2cHandshake<I, S>,
+        },
     }
 }
 
@@ -208,6 +643,9 @@ where
         match self.project().state.project() {
             UpgradeableConnStateProj::H1 { conn } => conn.graceful_shutdown(),
             UpgradeableConnStateProj::H2 { conn } => conn.graceful_shutdown(),
+            // The handshake hasn't produced a connection yet; dropping the
+            // future (when the caller stops polling) aborts the upgrade.
+            UpgradeableConnStateProj::H2c { .. } => {}
         }
     }
 }
@@ -225,10 +663,57 @@ where
     type Output = Result<()>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self.as_mut().project();
-        match this.state.as_mut().project() {
-            UpgradeableConnStateProj::H1 { conn } => conn.poll(cx).map_err(Into::into),
-            UpgradeableConnStateProj::H2 { conn } => conn.poll(cx).map_err(Into::into),
+        // Drive the per-connection lifecycle timers before the inner connection.
+        //
+        // Age: initiate a graceful shutdown once, when the timer elapses.
+        let age_fired = {
+            let lifecycle = self.as_mut().project().lifecycle;
+            !lifecycle.shutting_down
+                && lifecycle
+                    .age
+                    .as_mut()
+                    .is_some_and(|age| age.as_mut().poll(cx).is_ready())
+        };
+        if age_fired {
+            self.as_mut().project().lifecycle.shutting_down = true;
+            self.as_mut().graceful_shutdown();
+        }
+
+        // Idle: reset the timer whenever the IO made progress; if it fires with
+        // no progress, drop the connection.
+        {
+            let lifecycle = self.as_mut().project().lifecycle;
+            if let (Some(idle), Some(timeout)) =
+                (lifecycle.idle.as_mut(), lifecycle.idle_timeout)
+            {
+                if lifecycle
+                    .activity
+                    .swap(false, std::sync::atomic::Ordering::Relaxed)
+                {
+                    idle.as_mut().reset(tokio::time::Instant::now() + timeout);
+                }
+                if idle.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(Ok(()));
+                }
+            }
+        }
+
+        loop {
+            let mut this = self.as_mut().project();
+            match this.state.as_mut().project() {
+                UpgradeableConnStateProj::H1 { conn } => {
+                    return conn.poll(cx).map_err(Into::into);
+                }
+                UpgradeableConnStateProj::H2 { conn } => {
+                    return conn.poll(cx).map_err(Into::into);
+                }
+                UpgradeableConnStateProj::H2c { handshake } => {
+                    let conn = ready!(handshake.as_mut().poll(cx))?;
+                    // Handshake complete: become a regular HTTP/2 connection and
+                    // re-poll so the new state is driven this wakeup.
+                    this.state.set(UpgradeableConnState::H2 { conn });
+                }
+            }
         }
     }
 }
@@ -238,6 +723,9 @@ where
 pub(crate) struct Rewind<T> {
     pre: Option<Bytes>,
     inner: T,
+    // Flipped to `true` whenever a read or write makes progress, so the owning
+    // connection's idle timer can tell the IO is still active.
+    activity: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>,
 }
 
 impl<T> Rewind<T> {
@@ -245,6 +733,7 @@ impl<T> Rewind<T> {
         Rewind {
             pre: None,
             inner: io,
+            activity: None,
         }
     }
 
@@ -252,8 +741,33 @@ impl<T> Rewind<T> {
         Rewind {
             pre: Some(buf),
             inner: io,
+            activity: None,
+        }
+    }
+
+    /// Attach a shared flag that is set whenever this IO makes read/write
+    /// progress, used to feed the connection idle timer.
+    pub(crate) fn with_activity(
+        mut self,
+        activity: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    ) -> Self {
+        self.activity = Some(activity);
+        self
+    }
+
+    fn note_activity(&self) {
+        if let Some(activity) = &self.activity {
+            activity.store(true, std::sync::atomic::Ordering::Relaxed);
         }
     }
+
+    /// Replace any buffered read-ahead bytes with `bytes`, so the next reads
+    /// from this IO see `bytes` instead of whatever was previously rewound.
+    /// Used to splice the synthesized `h2c` stream-1 preface in place of the
+    /// raw HTTP/1.1 bytes that were sniffed off the wire.
+    pub(crate) fn prepend(&mut self, bytes: Bytes) {
+        self.pre = Some(bytes);
+    }
 }
 
 impl<T> AsyncRead for Rewind<T>
@@ -275,10 +789,15 @@ where
                     self.pre = Some(prefix);
                 }
 
+                self.note_activity();
                 return Poll::Ready(Ok(()));
             }
         }
-        Pin::new(&mut self.inner).poll_read(cx, buf)
+        let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = &res {
+            self.note_activity();
+        }
+        res
     }
 }
 
@@ -291,7 +810,11 @@ where
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        Pin::new(&mut self.inner).poll_write(cx, buf)
+        let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if matches!(res, Poll::Ready(Ok(_))) {
+            self.note_activity();
+        }
+        res
     }
 
     fn poll_write_vectored(
@@ -313,4 +836,334 @@ where
     fn is_write_vectored(&self) -> bool {
         self.inner.is_write_vectored()
     }
-}
\ No newline at end of file
+}
+/// A coordinator that drains a whole listener's worth of connections on
+/// shutdown.
+///
+/// `hyper-util`'s auto connection mishandles graceful shutdown (see the module
+/// comment), and [`UpgradeableConnection::graceful_shutdown`] only drives a
+/// single connection. [`GracefulShutdown`] hands a cloneable token to every
+/// spawned connection via [`watch`](Self::watch); when [`shutdown`](Self::shutdown)
+/// is triggered it signals every live connection to start a graceful shutdown
+/// and then waits for them to finish until the supplied deadline elapses.
+/// Connections still running at the deadline are not dropped by the
+/// coordinator; [`shutdown`](Self::shutdown) only stops waiting for them.
+#[derive(Clone)]
+pub struct GracefulShutdown {
+    inner: std::sync::Arc<GracefulInner>,
+}
+
+struct GracefulInner {
+    // Flipped to `true` exactly once, when shutdown is triggered. Watched
+    // connections observe this and begin their own graceful shutdown.
+    shutdown: tokio::sync::watch::Sender<bool>,
+    // Number of connections currently being tracked.
+    live: std::sync::atomic::AtomicUsize,
+    // Notified whenever a tracked connection finishes, so `shutdown` can wake
+    // and re-check whether the listener has fully drained.
+    drained: tokio::sync::Notify,
+}
+
+impl Default for GracefulShutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GracefulShutdown {
+    /// Create a new, un-triggered coordinator.
+    pub fn new() -> Self {
+        GracefulShutdown {
+            inner: std::sync::Arc::new(GracefulInner {
+                shutdown: tokio::sync::watch::Sender::new(false),
+                live: std::sync::atomic::AtomicUsize::new(0),
+                drained: tokio::sync::Notify::new(),
+            }),
+        }
+    }
+
+    /// Wrap a connection future so it is driven to completion but begins a
+    /// graceful shutdown as soon as this coordinator is triggered.
+    pub fn watch<I, S, B>(&self, conn: UpgradeableConnection<I, S>) -> GracefulConnection<I, S>
+    where
+        S: Service<Request<Incoming>, Response = Response<B>>,
+        I: AsyncRead + AsyncWrite + Unpin,
+        B: Body + 'static,
+    {
+        self.inner
+            .live
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let mut signal = self.inner.shutdown.subscribe();
+        GracefulConnection {
+            conn,
+            inner: self.inner.clone(),
+            // A future that resolves once the shutdown signal flips to `true`.
+            signal: Some(Box::pin(async move {
+                // `subscribe` starts at the current value, so if shutdown was
+                // already triggered `changed()` may never fire — check first.
+                if !*signal.borrow_and_update() {
+                    let _ = signal.changed().await;
+                }
+            })),
+        }
+    }
+
+    /// Trigger shutdown and wait for all tracked connections to finish, or for
+    /// `deadline` to elapse (whichever comes first). Returns `true` if every
+    /// connection drained within the deadline.
+    pub async fn shutdown(&self, deadline: std::time::Duration) -> bool {
+        // Signal every live connection to begin shutting down.
+        let _ = self.inner.shutdown.send(true);
+
+        let drain = async {
+            while self
+                .inner
+                .live
+                .load(std::sync::atomic::Ordering::SeqCst)
+                > 0
+            {
+                let notified = self.inner.drained.notified();
+                // Re-check after arming the notification to avoid a lost wakeup.
+                if self
+                    .inner
+                    .live
+                    .load(std::sync::atomic::Ordering::SeqCst)
+                    == 0
+                {
+                    break;
+                }
+                notified.await;
+            }
+        };
+
+        tokio::time::timeout(deadline, drain).await.is_ok()
+    }
+}
+
+pin_project! {
+    /// A connection future tracked by a [`GracefulShutdown`] coordinator.
+    pub struct GracefulConnection<I, S>
+    where
+        S: HttpService<Incoming>,
+    {
+        #[pin]
+        conn: UpgradeableConnection<I, S>,
+        inner: std::sync::Arc<GracefulInner>,
+        // `None` once the graceful shutdown has been initiated on `conn`.
+        signal: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    }
+
+    impl<I, S> PinnedDrop for GracefulConnection<I, S>
+    where
+        S: HttpService<Incoming>,
+    {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            this.inner
+                .live
+                .fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+            this.inner.drained.notify_waiters();
+        }
+    }
+}
+
+impl<I, S, B> Future for GracefulConnection<I, S>
+where
+    S: Service<Request<Incoming>, Response = Response<B>>,
+    S::Future: 'static,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: Body + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    TokioExecutor: Http2ServerConnExec<S::Future, B>,
+{
+    type Output = Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        if let Some(signal) = this.signal.as_mut() {
+            if signal.as_mut().poll(cx).is_ready() {
+                *this.signal = None;
+                this.conn.as_mut().graceful_shutdown();
+            }
+        }
+
+        this.conn.poll(cx)
+    }
+}
+
+/// An asynchronous source of incoming connections, mirroring the shape of the
+/// now-removed `hyper::server::accept::Accept` trait.
+///
+/// Implementors yield raw IO objects; the serve loop wraps each in [`Rewind`],
+/// sniffs its protocol version, and drives it to completion.
+pub trait Accept {
+    /// The IO type of an accepted connection.
+    type Conn: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+    /// The error type produced while accepting.
+    type Error: Into<Box<dyn StdError + Send + Sync>>;
+
+    /// Poll for the next incoming connection.
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<Self::Conn, Self::Error>>>;
+}
+
+impl Builder {
+    /// Run a complete accept → sniff → serve → spawn loop over `acceptor`.
+    ///
+    /// Each accepted connection is served with a clone of `service`, bounded by
+    /// a `max_connections` semaphore and registered with `graceful` so the
+    /// whole listener can be drained on shutdown. The loop runs until the
+    /// acceptor is exhausted or `graceful` is triggered; it then drains in-flight
+    /// connections within `drain_deadline`.
+    pub async fn serve<A, S, B>(
+        self,
+        mut acceptor: A,
+        service: S,
+        graceful: GracefulShutdown,
+        max_connections: usize,
+        drain_deadline: std::time::Duration,
+    ) where
+        A: Accept + Unpin,
+        S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+        S::Future: Send + 'static,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        B: Body + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+        A::Conn: 'static,
+        TokioExecutor: Http2ServerConnExec<S::Future, B>,
+        UpgradeableConnection<A::Conn, S>: Send + 'static,
+    {
+        let builder = std::sync::Arc::new(self);
+        let limiter = std::sync::Arc::new(tokio::sync::Semaphore::new(max_connections));
+        let mut shutdown = graceful.inner.shutdown.subscribe();
+
+        loop {
+            // A permit is held for the whole lifetime of the spawned connection.
+            let permit = limiter.clone().acquire_owned().await;
+            let Ok(permit) = permit else { break };
+
+            let conn = tokio::select! {
+                biased;
+                _ = shutdown.changed() => break,
+                conn = std::future::poll_fn(|cx| Pin::new(&mut acceptor).poll_accept(cx)) => conn,
+            };
+
+            let io = match conn {
+                Some(Ok(io)) => io,
+                // A transient accept error shouldn't tear down the whole loop.
+                Some(Err(_)) => continue,
+                None => break,
+            };
+
+            let builder = builder.clone();
+            let service = service.clone();
+            let graceful = graceful.clone();
+            tokio::spawn(async move {
+                let _permit = permit;
+                let Ok((version, io)) = builder.read_version(io).await else {
+                    return;
+                };
+                let conn = builder.serve_connection_with_upgrades(io, version, service);
+                let _ = graceful.watch(conn).await;
+            });
+        }
+
+        // Stop accepting and drain whatever is still in flight.
+        graceful.shutdown(drain_deadline).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt as _;
+
+    #[test]
+    fn wants_h2c_upgrade_detects_connection_and_upgrade_headers() {
+        let headers = b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade, HTTP2-Settings\r\nUpgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQAoAAA\r\n";
+        assert!(wants_h2c_upgrade(headers));
+    }
+
+    #[test]
+    fn wants_h2c_upgrade_requires_both_headers() {
+        let only_upgrade = b"GET / HTTP/1.1\r\nHost: example.com\r\nUpgrade: h2c\r\n";
+        assert!(!wants_h2c_upgrade(only_upgrade));
+
+        let only_connection = b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade\r\n";
+        assert!(!wants_h2c_upgrade(only_connection));
+    }
+
+    #[tokio::test]
+    async fn read_version_sniffs_h2_preface() {
+        let (mut client, server) = tokio::io::duplex(64);
+        client.write_all(H2_PREFACE).await.unwrap();
+        let (version, _io) = read_version(server, None).await.unwrap();
+        assert!(matches!(version, Version::H2));
+    }
+
+    #[tokio::test]
+    async fn read_version_sniffs_plain_h1() {
+        let (mut client, server) = tokio::io::duplex(256);
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await
+            .unwrap();
+        drop(client);
+        let (version, _io) = read_version(server, None).await.unwrap();
+        assert!(matches!(version, Version::H1));
+    }
+
+    #[tokio::test]
+    async fn read_version_sniffs_h2c_upgrade_request() {
+        let (mut client, server) = tokio::io::duplex(256);
+        let request =
+            b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n";
+        client.write_all(request).await.unwrap();
+        let (version, _io) = read_version(server, None).await.unwrap();
+        match version {
+            Version::H2c(sniffed) => assert_eq!(&sniffed[..], &request[..]),
+            _ => panic!("expected an h2c upgrade to be detected"),
+        }
+    }
+
+    #[test]
+    fn build_h2c_stream1_preface_emits_preface_settings_and_headers_frame() {
+        let request =
+            b"GET /widgets HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n";
+        let preface = build_h2c_stream1_preface(request).unwrap();
+
+        assert!(preface.starts_with(H2_PREFACE));
+        let after_preface = &preface[H2_PREFACE.len()..];
+
+        // SETTINGS frame header: empty payload, type 0x4, stream 0.
+        assert_eq!(&after_preface[0..3], &[0, 0, 0]);
+        assert_eq!(after_preface[3], 0x4);
+        assert_eq!(&after_preface[5..9], &[0, 0, 0, 0]);
+
+        // HEADERS frame follows immediately, for stream 1, END_HEADERS|END_STREAM.
+        let headers_frame = &after_preface[9..];
+        assert_eq!(headers_frame[3], 0x1);
+        assert_eq!(headers_frame[4], 0x4 | 0x1);
+        assert_eq!(&headers_frame[5..9], &[0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn hpack_literal_new_name_roundtrips_lengths() {
+        let mut out = Vec::new();
+        hpack_literal_new_name(&mut out, ":method", b"GET");
+        // Literal without indexing, new name: 0x00 prefix byte.
+        assert_eq!(out[0], 0x00);
+        // Name length-prefixed string, no Huffman (top bit clear).
+        assert_eq!(out[1], b":method".len() as u8);
+    }
+
+    #[tokio::test]
+    async fn graceful_shutdown_with_no_connections_completes_immediately() {
+        let graceful = GracefulShutdown::default();
+        graceful.shutdown(std::time::Duration::from_secs(1)).await;
+    }
+}
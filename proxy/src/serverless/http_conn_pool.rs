@@ -0,0 +1,241 @@
+//! Pool of multiplexed HTTP/2 connections to local-proxy / compute.
+//!
+//! The h2 path used to hand-roll its pooling directly on top of
+//! [`hyper1::client::conn::http2`] with no idle timeout, no maximum connection
+//! lifetime, and no per-host dial cap — the very machinery that was extracted
+//! out of `hyper::Client` into hyper-util's pooling client. This module keeps
+//! the in-repo pool (we need endpoint/aux-keyed accounting hyper-util's client
+//! can't express) but gives it those same semantics via [`ConnPoolConfig`]:
+//! bounded idle connections per host, eviction of stale/over-age connections
+//! before hand-out, and a GOAWAY health-check so a draining connection is
+//! dropped rather than returned to the next caller. The pool does not cap
+//! concurrent dials per host; dials are bounded only by the [`ApiLocks`]
+//! permit.
+//!
+//! [`ApiLocks`]: crate::console::locks::ApiLocks
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper1::client::conn::http2;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use parking_lot::Mutex;
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::context::RequestMonitoring;
+use crate::control_plane::messages::MetricsAuxInfo;
+
+use super::conn_pool::ConnInfo;
+
+/// Tuning for the h2 connection pool. Mirrors the knobs hyper-util's pooling
+/// client exposes, with one addition (`max_concurrent_streams_reuse`) that
+/// bounds how many in-flight requests we multiplex onto a single connection
+/// before dialing another.
+#[derive(Debug, Clone, Copy)]
+pub struct ConnPoolConfig {
+    /// Maximum number of idle connections retained per `Host`. Extra idle
+    /// connections are closed on return to the pool.
+    pub max_idle_per_host: usize,
+    /// Idle connections older than this (since last use) are evicted rather
+    /// than handed out.
+    pub idle_timeout: Duration,
+    /// Connections older than this (since they were dialed) are evicted
+    /// regardless of idleness, so long-lived multiplexed connections are
+    /// periodically refreshed.
+    pub max_lifetime: Duration,
+    /// How many concurrent streams we are willing to reuse on a single pooled
+    /// connection before preferring to open another one.
+    pub max_concurrent_streams_reuse: u32,
+}
+
+impl Default for ConnPoolConfig {
+    fn default() -> Self {
+        ConnPoolConfig {
+            max_idle_per_host: 8,
+            idle_timeout: Duration::from_secs(60),
+            max_lifetime: Duration::from_secs(600),
+            max_concurrent_streams_reuse: 100,
+        }
+    }
+}
+
+/// A pooled h2 connection plus the bookkeeping the pool needs to age it out and
+/// health-check it.
+struct PoolEntry {
+    sender: http2::SendRequest<Full<Bytes>>,
+    /// When the underlying connection was dialed, for `max_lifetime`.
+    created_at: Instant,
+    /// When the connection was dialed or last handed out, for `idle_timeout`.
+    idle_since: Instant,
+    /// Streams currently checked out against this connection, shared with
+    /// every outstanding [`Client`] handed out for it, so [`GlobalConnPool::get`]
+    /// can respect `max_concurrent_streams_reuse`.
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl PoolEntry {
+    /// A connection is healthy only if its sender is still open (not GOAWAY'd or
+    /// otherwise closed) and it is within both the idle and lifetime bounds.
+    fn is_healthy(&self, config: &ConnPoolConfig, now: Instant) -> bool {
+        !self.sender.is_closed()
+            && now.duration_since(self.idle_since) < config.idle_timeout
+            && now.duration_since(self.created_at) < config.max_lifetime
+    }
+}
+
+/// A checked-out h2 client. Dropping it releases its stream slot on the
+/// underlying connection, which stays in the pool from the time it is dialed
+/// (see [`poll_http2_client`]) until it is evicted.
+pub struct Client {
+    inner: http2::SendRequest<Full<Bytes>>,
+    aux: MetricsAuxInfo,
+    /// Shared with the [`PoolEntry`] of the connection this client runs on;
+    /// both [`GlobalConnPool::get`] and [`poll_http2_client`] set it.
+    /// Decremented on drop so the pool can tell how many streams are actually
+    /// in flight against a connection.
+    in_flight: Option<Arc<AtomicUsize>>,
+}
+
+impl Client {
+    pub fn inner(&self) -> &http2::SendRequest<Full<Bytes>> {
+        &self.inner
+    }
+
+    pub fn aux(&self) -> &MetricsAuxInfo {
+        &self.aux
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Some(in_flight) = &self.in_flight {
+            in_flight.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The process-wide h2 connection pool, keyed by `Host`.
+pub struct GlobalConnPool {
+    config: ConnPoolConfig,
+    idle: Mutex<HashMap<String, VecDeque<PoolEntry>>>,
+}
+
+impl GlobalConnPool {
+    pub fn new(config: ConnPoolConfig) -> Arc<Self> {
+        Arc::new(GlobalConnPool {
+            config,
+            idle: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn config(&self) -> &ConnPoolConfig {
+        &self.config
+    }
+
+    /// Hand out a healthy pooled connection for `conn_info`, or `None` if the
+    /// pool has nothing reusable. Stale/over-age/GOAWAY'd entries are dropped as
+    /// they are encountered rather than returned.
+    pub fn get(&self, _ctx: &RequestMonitoring, conn_info: &ConnInfo) -> Option<Client> {
+        let host = conn_info.host_str();
+        let now = Instant::now();
+        let mut idle = self.idle.lock();
+        let queue = idle.get_mut(&host)?;
+
+        // Visit each entry at most once: unhealthy ones are dropped outright,
+        // entries already at `max_concurrent_streams_reuse` are left parked at
+        // the back for a later caller rather than handed out again here.
+        for _ in 0..queue.len() {
+            let Some(mut entry) = queue.pop_front() else {
+                break;
+            };
+            if !entry.is_healthy(&self.config, now) {
+                debug!(%host, "h2 pool: dropping stale/closed connection");
+                // `entry` is dropped here, closing the connection.
+                continue;
+            }
+            if entry.in_flight.load(Ordering::Relaxed) >= self.config.max_concurrent_streams_reuse as usize {
+                debug!(%host, "h2 pool: connection at max_concurrent_streams_reuse, trying next");
+                queue.push_back(entry);
+                continue;
+            }
+
+            // Refresh idle timestamp so a long-lived reused connection still
+            // ages out via `max_lifetime` rather than `idle_timeout`.
+            entry.idle_since = now;
+            entry.in_flight.fetch_add(1, Ordering::Relaxed);
+            let client = Client {
+                inner: entry.sender.clone(),
+                aux: conn_info.aux.clone(),
+                in_flight: Some(entry.in_flight.clone()),
+            };
+            // Put it back: h2 is multiplexed, so the connection can serve
+            // more callers concurrently up to `max_concurrent_streams_reuse`.
+            queue.push_back(entry);
+            debug!(%host, "h2 pool: reusing connection");
+            return Some(client);
+        }
+        None
+    }
+
+    /// Return a freshly-dialed connection to the pool, evicting the oldest idle
+    /// entry when the per-host cap is exceeded.
+    fn put(&self, host: String, entry: PoolEntry) {
+        let mut idle = self.idle.lock();
+        let queue = idle.entry(host).or_default();
+        queue.push_back(entry);
+        while queue.len() > self.config.max_idle_per_host {
+            queue.pop_front();
+        }
+    }
+}
+
+/// Register a newly-established h2 connection with the pool and spawn the task
+/// that drives it. Returns a [`Client`] for immediate use; the connection is
+/// added to `pool` for reuse right away, before the driving task starts.
+pub fn poll_http2_client(
+    pool: Arc<GlobalConnPool>,
+    ctx: &RequestMonitoring,
+    conn_info: ConnInfo,
+    client: http2::SendRequest<Full<Bytes>>,
+    connection: http2::Connection<TokioIo<TcpStream>, Full<Bytes>, TokioExecutor>,
+    conn_id: uuid::Uuid,
+    aux: MetricsAuxInfo,
+) -> Client {
+    let host = conn_info.host_str();
+    let now = Instant::now();
+    // The caller of `poll_http2_client` is itself a stream against this fresh
+    // connection, so the counter starts at one rather than zero.
+    let in_flight = Arc::new(AtomicUsize::new(1));
+
+    pool.put(
+        host.clone(),
+        PoolEntry {
+            sender: client.clone(),
+            created_at: now,
+            idle_since: now,
+            in_flight: in_flight.clone(),
+        },
+    );
+
+    let session_id = ctx.session_id();
+    tokio::spawn(async move {
+        match connection.await {
+            Ok(()) => info!(%conn_id, %session_id, "h2 connection to compute closed"),
+            Err(e) => info!(%conn_id, %session_id, "h2 connection to compute errored: {e}"),
+        }
+        // The connection is gone; the next `get` will see `is_closed()` and
+        // evict the entry, so there's nothing further to do here.
+    });
+
+    Client {
+        inner: client,
+        aux,
+        in_flight: Some(in_flight),
+    }
+}
@@ -0,0 +1,279 @@
+//! Persisted location of a usage snapshot upload, plus the exactly-once upload
+//! state machine that drives it.
+//!
+//! Uploads are idempotent: each is keyed by an [`IdempotencyKey`] so a retried
+//! or resumed run writes to the same object rather than producing duplicates.
+//! [`Location`] names where the snapshot lives. Historically only `LocalFs`
+//! (testing) and `AwsS3` were supported; non-AWS deployments had to shim
+//! through an S3-compatible gateway. This module adds native `GcsBucket` and
+//! `AzureBlob` targets so the same state machine can drive them directly.
+
+use anyhow::Context;
+use aws_sdk_s3::primitives::ByteStream;
+use azure_storage::prelude::*;
+use azure_storage_blobs::prelude::*;
+use google_cloud_storage::client::{Client as GcsClient, ClientConfig as GcsClientConfig};
+use google_cloud_storage::http::objects::get::GetObjectRequest;
+use google_cloud_storage::http::objects::upload::{Media, UploadObjectRequest, UploadType};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+
+/// Shared S3 client, built lazily from the environment/instance-profile
+/// credential chain on first use rather than at process startup.
+static S3_CLIENT: OnceCell<aws_sdk_s3::Client> = OnceCell::const_new();
+
+async fn s3_client() -> &'static aws_sdk_s3::Client {
+    S3_CLIENT
+        .get_or_init(|| async {
+            let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+            aws_sdk_s3::Client::new(&config)
+        })
+        .await
+}
+
+/// Shared GCS client, built lazily from the environment's application-default
+/// credentials on first use rather than at process startup.
+static GCS_CLIENT: OnceCell<GcsClient> = OnceCell::const_new();
+
+async fn gcs_client() -> anyhow::Result<&'static GcsClient> {
+    GCS_CLIENT
+        .get_or_try_init(|| async {
+            let config = GcsClientConfig::default().with_auth().await?;
+            Ok(GcsClient::new(config))
+        })
+        .await
+}
+
+/// Shared Azure Blob Storage client, built lazily from the
+/// `AZURE_STORAGE_ACCOUNT`/`AZURE_STORAGE_ACCESS_KEY` environment variables on
+/// first use rather than at process startup.
+static AZURE_CLIENT: OnceCell<BlobServiceClient> = OnceCell::const_new();
+
+async fn azure_client() -> anyhow::Result<&'static BlobServiceClient> {
+    AZURE_CLIENT
+        .get_or_try_init(|| async {
+            let account = std::env::var("AZURE_STORAGE_ACCOUNT")
+                .context("AZURE_STORAGE_ACCOUNT must be set to use Location::AzureBlob")?;
+            let access_key = std::env::var("AZURE_STORAGE_ACCESS_KEY")
+                .context("AZURE_STORAGE_ACCESS_KEY must be set to use Location::AzureBlob")?;
+            let credentials = StorageCredentials::access_key(account.clone(), access_key);
+            Ok(BlobServiceClient::new(account, credentials))
+        })
+        .await
+}
+
+/// A key that uniquely identifies one usage-snapshot upload window, so resuming
+/// or retrying writes to the same object instead of duplicating it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotencyKey(pub String);
+
+/// Where a usage snapshot is (or will be) persisted.
+///
+/// `serde` is `V1`-tagged so existing persisted `AwsS3` state continues to
+/// deserialize unchanged as new variants are added.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Location {
+    /// Local filesystem, used by tests.
+    LocalFs { path: camino::Utf8PathBuf },
+    /// An object in an AWS S3 bucket.
+    AwsS3 { bucket: String, key: String },
+    /// An object in a Google Cloud Storage bucket.
+    GcsBucket { bucket: String, object: String },
+    /// A blob in an Azure Blob Storage container.
+    AzureBlob { container: String, blob: String },
+}
+
+/// The persisted upload state, versioned for forward compatibility.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Root {
+    V1(V1),
+}
+
+/// Version 1 of the upload state: either in progress toward a [`Location`], or
+/// done at one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum V1 {
+    InProgress {
+        idempotency_key: IdempotencyKey,
+        location: Location,
+    },
+    Done {
+        idempotency_key: IdempotencyKey,
+        location: Location,
+    },
+}
+
+impl Root {
+    /// The idempotency key of this upload, regardless of state. Kept as an
+    /// accessor so callers don't need to match on the version/variant.
+    pub fn idempotency_key(&self) -> &IdempotencyKey {
+        match self {
+            Root::V1(V1::InProgress {
+                idempotency_key, ..
+            })
+            | Root::V1(V1::Done {
+                idempotency_key, ..
+            }) => idempotency_key,
+        }
+    }
+
+    /// The location this upload targets.
+    pub fn location(&self) -> &Location {
+        match self {
+            Root::V1(V1::InProgress { location, .. }) | Root::V1(V1::Done { location, .. }) => {
+                location
+            }
+        }
+    }
+
+    /// Begin a new upload: an `InProgress` root for `location`, to be
+    /// persisted by the caller before any bytes are written so a crash before
+    /// [`Self::resume`] completes is still resumable against the same object.
+    pub fn begin(idempotency_key: IdempotencyKey, location: Location) -> Root {
+        Root::V1(V1::InProgress {
+            idempotency_key,
+            location,
+        })
+    }
+
+    /// Drive an `InProgress` root to `Done`, exactly once. If `data` was
+    /// already written at this `Root`'s location — e.g. a crash happened
+    /// between the write and the state transition on a prior attempt — the
+    /// write is skipped and the root is simply transitioned. A `Done` root is
+    /// returned unchanged.
+    pub async fn resume(self, data: &[u8]) -> anyhow::Result<Root> {
+        match self {
+            Root::V1(V1::Done { .. }) => Ok(self),
+            Root::V1(V1::InProgress {
+                idempotency_key,
+                location,
+            }) => {
+                if !location.exists().await? {
+                    location.put(data).await?;
+                }
+                Ok(Root::V1(V1::Done {
+                    idempotency_key,
+                    location,
+                }))
+            }
+        }
+    }
+}
+
+impl Location {
+    /// Write `data` to this location. The caller has already recorded an
+    /// `InProgress` [`Root`] with the matching idempotency key, so a crash
+    /// between here and `Done` is safely resumable against the same object.
+    pub async fn put(&self, data: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Location::LocalFs { path } => {
+                tokio::fs::write(path, data).await?;
+                Ok(())
+            }
+            Location::AwsS3 { bucket, key } => put_s3(bucket, key, data).await,
+            Location::GcsBucket { bucket, object } => put_gcs(bucket, object, data).await,
+            Location::AzureBlob { container, blob } => put_azure(container, blob, data).await,
+        }
+    }
+
+    /// Whether an object already exists at this location, used to resume an
+    /// `InProgress` upload without re-writing a completed one.
+    pub async fn exists(&self) -> anyhow::Result<bool> {
+        match self {
+            Location::LocalFs { path } => Ok(tokio::fs::try_exists(path).await?),
+            Location::AwsS3 { bucket, key } => head_s3(bucket, key).await,
+            Location::GcsBucket { bucket, object } => head_gcs(bucket, object).await,
+            Location::AzureBlob { container, blob } => head_azure(container, blob).await,
+        }
+    }
+}
+
+// The backend-specific uploads are thin wrappers over the respective object
+// store clients; kept behind these functions so `Location::put`/`exists` stay
+// backend-agnostic.
+
+async fn put_s3(bucket: &str, key: &str, data: &[u8]) -> anyhow::Result<()> {
+    s3_client()
+        .await
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .body(ByteStream::from(data.to_vec()))
+        .send()
+        .await?;
+    Ok(())
+}
+async fn head_s3(bucket: &str, key: &str) -> anyhow::Result<bool> {
+    match s3_client()
+        .await
+        .head_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(aws_sdk_s3::error::SdkError::ServiceError(e)) if e.err().is_not_found() => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+async fn put_gcs(bucket: &str, object: &str, data: &[u8]) -> anyhow::Result<()> {
+    let upload_type = UploadType::Simple(Media::new(object.to_string()));
+    gcs_client()
+        .await?
+        .upload_object(
+            &UploadObjectRequest {
+                bucket: bucket.to_string(),
+                ..Default::default()
+            },
+            data.to_vec(),
+            &upload_type,
+        )
+        .await?;
+    Ok(())
+}
+async fn head_gcs(bucket: &str, object: &str) -> anyhow::Result<bool> {
+    let request = GetObjectRequest {
+        bucket: bucket.to_string(),
+        object: object.to_string(),
+        ..Default::default()
+    };
+    match gcs_client().await?.get_object(&request).await {
+        Ok(_) => Ok(true),
+        Err(google_cloud_storage::http::Error::Response(e)) if e.code == 404 => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+async fn put_azure(container: &str, blob: &str, data: &[u8]) -> anyhow::Result<()> {
+    azure_client()
+        .await?
+        .container_client(container)
+        .blob_client(blob)
+        .put_block_blob(data.to_vec())
+        .content_type("application/octet-stream")
+        .into_future()
+        .await?;
+    Ok(())
+}
+async fn head_azure(container: &str, blob: &str) -> anyhow::Result<bool> {
+    match azure_client()
+        .await?
+        .container_client(container)
+        .blob_client(blob)
+        .get_properties()
+        .into_future()
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(e)
+            if matches!(
+                e.kind(),
+                azure_core::error::ErrorKind::HttpResponse { status, .. }
+                    if *status == azure_core::StatusCode::NotFound
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(e) => Err(e.into()),
+    }
+}